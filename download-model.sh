@@ -4,6 +4,7 @@
 # Usage: ./download-model.sh [ENGINE]
 #   Engines: moonshine-tiny, moonshine-base,
 #            whisper-tiny, whisper-base, whisper-small, whisper-medium,
+#            whisper-tiny-en, whisper-base-en, whisper-small-en, whisper-medium-en,
 #            whisper-large-v3, whisper-large-v3-turbo,
 #            parakeet-tdt-v3, parakeet-tdt-v3-int8, all
 # Default: parakeet-tdt-v3-int8
@@ -165,16 +166,28 @@ case "$ENGINE" in
         download_moonshine_base
         ;;
     whisper-tiny)
-        download_whisper_model "tiny" "ggml-tiny.en.bin"
+        download_whisper_model "tiny" "ggml-tiny.bin"
+        ;;
+    whisper-tiny-en)
+        download_whisper_model "tiny.en" "ggml-tiny.en.bin"
         ;;
     whisper-base)
-        download_whisper_model "base" "ggml-base.en.bin"
+        download_whisper_model "base" "ggml-base.bin"
+        ;;
+    whisper-base-en)
+        download_whisper_model "base.en" "ggml-base.en.bin"
         ;;
     whisper-small)
-        download_whisper_model "small" "ggml-small.en.bin"
+        download_whisper_model "small" "ggml-small.bin"
+        ;;
+    whisper-small-en)
+        download_whisper_model "small.en" "ggml-small.en.bin"
         ;;
     whisper-medium)
-        download_whisper_model "medium" "ggml-medium.en.bin"
+        download_whisper_model "medium" "ggml-medium.bin"
+        ;;
+    whisper-medium-en)
+        download_whisper_model "medium.en" "ggml-medium.en.bin"
         ;;
     whisper-large-v3)
         download_whisper_model "large-v3" "ggml-large-v3.bin"
@@ -191,10 +204,14 @@ case "$ENGINE" in
     all)
         download_moonshine_tiny
         download_moonshine_base
-        download_whisper_model "tiny" "ggml-tiny.en.bin"
-        download_whisper_model "base" "ggml-base.en.bin"
-        download_whisper_model "small" "ggml-small.en.bin"
-        download_whisper_model "medium" "ggml-medium.en.bin"
+        download_whisper_model "tiny" "ggml-tiny.bin"
+        download_whisper_model "tiny.en" "ggml-tiny.en.bin"
+        download_whisper_model "base" "ggml-base.bin"
+        download_whisper_model "base.en" "ggml-base.en.bin"
+        download_whisper_model "small" "ggml-small.bin"
+        download_whisper_model "small.en" "ggml-small.en.bin"
+        download_whisper_model "medium" "ggml-medium.bin"
+        download_whisper_model "medium.en" "ggml-medium.en.bin"
         download_whisper_model "large-v3" "ggml-large-v3.bin"
         download_whisper_model "large-v3-turbo" "ggml-large-v3-turbo.bin"
         download_parakeet_tdt_v3
@@ -202,7 +219,7 @@ case "$ENGINE" in
         ;;
     *)
         echo "Unknown engine: $ENGINE" >&2
-        echo "Usage: $0 [moonshine-tiny|moonshine-base|whisper-tiny|whisper-base|whisper-small|whisper-medium|whisper-large-v3|whisper-large-v3-turbo|parakeet-tdt-v3|parakeet-tdt-v3-int8|all]" >&2
+        echo "Usage: $0 [moonshine-tiny|moonshine-base|whisper-tiny|whisper-base|whisper-small|whisper-medium|whisper-tiny-en|whisper-base-en|whisper-small-en|whisper-medium-en|whisper-large-v3|whisper-large-v3-turbo|parakeet-tdt-v3|parakeet-tdt-v3-int8|all]" >&2
         exit 1
         ;;
 esac
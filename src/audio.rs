@@ -0,0 +1,249 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::Path;
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{FromSample, Sample, SampleFormat};
+
+use crate::SAMPLE_RATE;
+
+/// Preference order for capture sample formats, lower is better. Float formats need no
+/// conversion; wider integer formats keep more headroom than narrow ones.
+pub fn format_preference(format: SampleFormat) -> u8 {
+    match format {
+        SampleFormat::F32 => 0,
+        SampleFormat::I16 => 1,
+        SampleFormat::I32 => 2,
+        SampleFormat::F64 => 3,
+        SampleFormat::U16 => 4,
+        SampleFormat::I64 => 5,
+        SampleFormat::U32 => 6,
+        SampleFormat::U64 => 7,
+        SampleFormat::I8 => 8,
+        SampleFormat::U8 => 9,
+        _ => u8::MAX,
+    }
+}
+
+/// Average one interleaved frame into a single normalized f32 sample in [-1.0, 1.0].
+pub fn downmix<T>(frame: &[T]) -> f32
+where
+    T: Sample,
+    f32: FromSample<T>,
+{
+    if frame.is_empty() {
+        return 0.0;
+    }
+    frame.iter().map(|&s| s.to_sample::<f32>()).sum::<f32>() / frame.len() as f32
+}
+
+/// Append `new` to the rolling pre-roll, keeping only the most recent `max` samples.
+pub fn roll_preroll(preroll: &mut Vec<f32>, new: &[f32], max: usize) {
+    if new.len() >= max {
+        preroll.clear();
+        preroll.extend_from_slice(&new[new.len() - max..]);
+        return;
+    }
+    let excess = (preroll.len() + new.len()).saturating_sub(max);
+    preroll.drain(..excess);
+    preroll.extend_from_slice(new);
+}
+
+/// Prepend the pre-roll to the start of `recording`, keeping only the most recent
+/// `max` samples of it.
+pub fn splice_preroll(preroll: &[f32], max: usize, recording: &mut Vec<f32>) {
+    let preroll = &preroll[preroll.len().saturating_sub(max)..];
+    recording.splice(0..0, preroll.iter().copied());
+}
+
+/// Pick a device from `names` by index, exact name or substring (case-insensitive),
+/// in that order of precedence.
+pub fn select_device(names: &[String], query: &str) -> Option<usize> {
+    let query = query.trim();
+    if let Ok(index) = query.parse::<usize>() {
+        if index < names.len() {
+            return Some(index);
+        }
+    }
+
+    let query = query.to_lowercase();
+    names
+        .iter()
+        .position(|name| name.to_lowercase() == query)
+        .or_else(|| {
+            names
+                .iter()
+                .position(|name| name.to_lowercase().contains(&query))
+        })
+}
+
+/// Names of the available input devices, in the order `select_device` indexes them.
+pub fn input_device_names() -> Vec<String> {
+    match cpal::default_host().input_devices() {
+        Ok(devices) => devices.map(|d| d.name().unwrap_or_default()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Find the input device matching `query` by index, exact name or substring.
+pub fn find_input_device(query: &str) -> Option<cpal::Device> {
+    let devices: Vec<cpal::Device> = cpal::default_host().input_devices().ok()?.collect();
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| d.name().unwrap_or_default())
+        .collect();
+    let index = select_device(&names, query)?;
+    devices.into_iter().nth(index)
+}
+
+pub fn default_input_device() -> Option<cpal::Device> {
+    cpal::default_host().default_input_device()
+}
+
+/// Save mono `SAMPLE_RATE` audio as a 16-bit WAV file.
+pub fn write_wav(path: &Path, samples: &[f32]) -> Result<(), hound::Error> {
+    write_wav_to(BufWriter::new(File::create(path)?), samples)
+}
+
+/// Like `write_wav`, into an already open file.
+pub fn write_wav_to<W: Write + Seek>(out: W, samples: &[f32]) -> Result<(), hound::Error> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::new(out, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample.to_sample::<i16>())?;
+    }
+    writer.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downmix_f32() {
+        assert_eq!(downmix(&[0.5f32]), 0.5);
+        assert_eq!(downmix(&[0.5f32, -0.5]), 0.0);
+        assert_eq!(downmix(&[0.25f32, 0.75]), 0.5);
+    }
+
+    #[test]
+    fn test_downmix_i16() {
+        assert_eq!(downmix(&[0i16]), 0.0);
+        assert_eq!(downmix(&[i16::MIN]), -1.0);
+        assert!((downmix(&[i16::MAX]) - 1.0).abs() < 1e-4);
+        assert!((downmix(&[16384i16, 16384]) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_downmix_u16() {
+        // Unsigned formats are centred on the midpoint
+        assert_eq!(downmix(&[32768u16]), 0.0);
+        assert_eq!(downmix(&[0u16]), -1.0);
+        assert!((downmix(&[u16::MAX]) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_downmix_i32() {
+        assert_eq!(downmix(&[0i32]), 0.0);
+        assert_eq!(downmix(&[i32::MIN]), -1.0);
+        assert!((downmix(&[i32::MAX / 2, i32::MAX / 2]) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_downmix_empty_frame() {
+        assert_eq!(downmix::<f32>(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_roll_preroll_keeps_latest() {
+        let mut preroll = Vec::new();
+        roll_preroll(&mut preroll, &[1.0, 2.0, 3.0], 4);
+        assert_eq!(preroll, vec![1.0, 2.0, 3.0]);
+        roll_preroll(&mut preroll, &[4.0, 5.0], 4);
+        assert_eq!(preroll, vec![2.0, 3.0, 4.0, 5.0]);
+        roll_preroll(&mut preroll, &[6.0, 7.0, 8.0, 9.0, 10.0], 4);
+        assert_eq!(preroll, vec![7.0, 8.0, 9.0, 10.0]);
+    }
+
+    #[test]
+    fn test_roll_preroll_disabled() {
+        let mut preroll = Vec::new();
+        roll_preroll(&mut preroll, &[1.0, 2.0], 0);
+        assert!(preroll.is_empty());
+    }
+
+    #[test]
+    fn test_splice_preroll() {
+        let mut recording = vec![4.0, 5.0];
+        splice_preroll(&[1.0, 2.0, 3.0], 8, &mut recording);
+        assert_eq!(recording, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_splice_preroll_truncates_to_latest() {
+        let mut recording = vec![4.0];
+        splice_preroll(&[1.0, 2.0, 3.0], 2, &mut recording);
+        assert_eq!(recording, vec![2.0, 3.0, 4.0]);
+
+        let mut recording = vec![4.0];
+        splice_preroll(&[1.0, 2.0, 3.0], 0, &mut recording);
+        assert_eq!(recording, vec![4.0]);
+    }
+
+    #[test]
+    fn test_splice_preroll_empty() {
+        let mut recording = Vec::new();
+        splice_preroll(&[], 10, &mut recording);
+        assert!(recording.is_empty());
+
+        splice_preroll(&[1.0], 10, &mut recording);
+        assert_eq!(recording, vec![1.0]);
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_select_device_by_index() {
+        let devices = names(&["Webcam Mic", "USB Headset"]);
+        assert_eq!(select_device(&devices, "1"), Some(1));
+        assert_eq!(select_device(&devices, " 0 "), Some(0));
+        assert_eq!(select_device(&devices, "2"), None);
+    }
+
+    #[test]
+    fn test_select_device_exact_before_substring() {
+        let devices = names(&["USB Headset Monitor", "usb headset"]);
+        assert_eq!(select_device(&devices, "USB Headset"), Some(1));
+        assert_eq!(select_device(&devices, "monitor"), Some(0));
+    }
+
+    #[test]
+    fn test_select_device_substring() {
+        let devices = names(&["Webcam Mic", "Jabra Evolve2 65"]);
+        assert_eq!(select_device(&devices, "jabra"), Some(1));
+        assert_eq!(select_device(&devices, "Evolve2"), Some(1));
+        assert_eq!(select_device(&devices, "blue yeti"), None);
+    }
+
+    #[test]
+    fn test_select_device_numeric_name() {
+        // An out-of-range number can still match a device name
+        let devices = names(&["Mic 7"]);
+        assert_eq!(select_device(&devices, "7"), Some(0));
+    }
+
+    #[test]
+    fn test_format_preference() {
+        assert!(format_preference(SampleFormat::F32) < format_preference(SampleFormat::I16));
+        assert!(format_preference(SampleFormat::I16) < format_preference(SampleFormat::I32));
+        assert!(format_preference(SampleFormat::I32) < format_preference(SampleFormat::U16));
+        assert!(format_preference(SampleFormat::U16) < format_preference(SampleFormat::U8));
+    }
+}
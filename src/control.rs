@@ -0,0 +1,354 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1, SIGUSR2};
+use signal_hook::iterator::Signals;
+
+use crate::{get_socket_path, DaemonState};
+
+/// A command sent to the daemon over the control socket, one JSON object per line.
+/// Example: `{"command": "start"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    Start,
+    /// With `wait`, the reply is held back until transcription finishes and carries the text.
+    Stop {
+        #[serde(default)]
+        wait: bool,
+    },
+    Cancel,
+    Toggle {
+        #[serde(default)]
+        wait: bool,
+    },
+    /// Hands-free dictation until `stop`, typing each utterance at a pause.
+    Listen,
+    /// Load another engine in the background and switch to it once it's ready. With
+    /// `wait`, the reply is held back until then.
+    SetEngine {
+        engine: String,
+        #[serde(default)]
+        wait: bool,
+    },
+    Status,
+    Shutdown,
+}
+
+/// The daemon's answer to a single command.
+/// Example: `{"ok": true, "state": "recording"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reply {
+    pub ok: bool,
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl Reply {
+    pub fn ok(state: DaemonState) -> Self {
+        Reply {
+            ok: true,
+            state: state.as_str().to_string(),
+            error: None,
+            text: None,
+        }
+    }
+
+    pub fn with_text(mut self, text: String) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    pub fn error(state: DaemonState, msg: &str) -> Self {
+        Reply {
+            ok: false,
+            state: state.as_str().to_string(),
+            error: Some(msg.to_string()),
+            text: None,
+        }
+    }
+}
+
+/// A command queued for the dispatcher. Requests from the socket carry a reply
+/// channel; requests from legacy signals don't.
+pub struct ControlRequest {
+    pub command: Command,
+    reply: Option<Sender<Reply>>,
+}
+
+impl ControlRequest {
+    pub fn new(command: Command) -> Self {
+        ControlRequest {
+            command,
+            reply: None,
+        }
+    }
+
+    pub fn with_reply(command: Command, reply: Sender<Reply>) -> Self {
+        ControlRequest {
+            command,
+            reply: Some(reply),
+        }
+    }
+
+    /// Send the reply back to the client, if there is one waiting.
+    pub fn respond(&self, reply: Reply) {
+        if let Some(ref tx) = self.reply {
+            let _ = tx.send(reply);
+        }
+    }
+}
+
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    serde_json::from_str(line.trim()).map_err(|e| format!("invalid command: {}", e))
+}
+
+/// Turn the legacy signals into commands on `commands`: USR1 starts, USR2 stops, and
+/// TERM or INT shuts down.
+pub fn spawn_signal_listener(commands: Sender<ControlRequest>) -> io::Result<()> {
+    let mut signals = Signals::new([SIGUSR1, SIGUSR2, SIGTERM, SIGINT])?;
+    thread::spawn(move || {
+        for sig in signals.forever() {
+            let command = match sig {
+                SIGUSR1 => Command::Start,
+                SIGUSR2 => Command::Stop { wait: false },
+                SIGTERM | SIGINT => Command::Shutdown,
+                _ => continue,
+            };
+            if commands.send(ControlRequest::new(command)).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Bind the control socket and forward incoming commands to `commands`.
+/// A leftover socket file from a previous run is removed first.
+pub fn spawn_listener(path: &Path, commands: Sender<ControlRequest>) -> io::Result<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    info!("Control socket listening on {:?}", path);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let commands = commands.clone();
+                    thread::spawn(move || handle_connection(stream, commands));
+                }
+                Err(e) => warn!("Control socket accept failed: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(stream: UnixStream, commands: Sender<ControlRequest>) {
+    let mut writer = match stream.try_clone() {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Control socket clone failed: {}", e);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match parse_command(&line) {
+            Ok(command) => {
+                debug!("Control command: {:?}", command);
+                let (tx, rx) = mpsc::channel();
+                if commands
+                    .send(ControlRequest::with_reply(command, tx))
+                    .is_err()
+                {
+                    break;
+                }
+                match rx.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => break,
+                }
+            }
+            Err(e) => Reply {
+                ok: false,
+                state: String::new(),
+                error: Some(e),
+                text: None,
+            },
+        };
+
+        let json = serde_json::to_string(&reply).unwrap_or_default();
+        if writeln!(writer, "{}", json).is_err() {
+            break;
+        }
+    }
+}
+
+/// Send a single command to the running daemon and wait for its reply.
+pub fn send_command(command: &Command) -> io::Result<Reply> {
+    send_command_to(&get_socket_path(), command)
+}
+
+pub fn send_command_to(path: &Path, command: &Command) -> io::Result<Reply> {
+    let mut stream = UnixStream::connect(path)?;
+    let json = serde_json::to_string(command).map_err(io::Error::other)?;
+    writeln!(stream, "{}", json)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    if line.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "daemon closed the connection",
+        ));
+    }
+    serde_json::from_str(&line).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command(r#"{"command":"start"}"#), Ok(Command::Start));
+        assert_eq!(
+            parse_command(r#"{"command":"stop"}"#),
+            Ok(Command::Stop { wait: false })
+        );
+        assert_eq!(
+            parse_command(r#"{"command":"stop","wait":true}"#),
+            Ok(Command::Stop { wait: true })
+        );
+        assert_eq!(
+            parse_command(r#"{"command":"cancel"}"#),
+            Ok(Command::Cancel)
+        );
+        assert_eq!(
+            parse_command(r#"{"command":"toggle"}"#),
+            Ok(Command::Toggle { wait: false })
+        );
+        assert_eq!(
+            parse_command(r#"{"command":"listen"}"#),
+            Ok(Command::Listen)
+        );
+        assert_eq!(
+            parse_command(r#"{"command":"set_engine","engine":"whisper-base"}"#),
+            Ok(Command::SetEngine {
+                engine: "whisper-base".to_string(),
+                wait: false
+            })
+        );
+        assert_eq!(
+            parse_command(r#"{"command":"status"}"#),
+            Ok(Command::Status)
+        );
+        assert_eq!(
+            parse_command(" {\"command\": \"shutdown\"}\n"),
+            Ok(Command::Shutdown)
+        );
+    }
+
+    #[test]
+    fn test_parse_command_invalid() {
+        assert!(parse_command(r#"{"command":"explode"}"#).is_err());
+        assert!(parse_command("start").is_err());
+        assert!(parse_command("{}").is_err());
+        assert!(parse_command(r#"{"command":"set_engine"}"#).is_err());
+    }
+
+    #[test]
+    fn test_reply_json() {
+        let json = serde_json::to_string(&Reply::ok(DaemonState::Recording)).unwrap();
+        assert_eq!(json, r#"{"ok":true,"state":"recording"}"#);
+
+        let json =
+            serde_json::to_string(&Reply::error(DaemonState::Idle, "not recording")).unwrap();
+        assert_eq!(
+            json,
+            r#"{"ok":false,"state":"idle","error":"not recording"}"#
+        );
+
+        let json = serde_json::to_string(&Reply::ok(DaemonState::Idle).with_text("hi".to_string()))
+            .unwrap();
+        assert_eq!(json, r#"{"ok":true,"state":"idle","text":"hi"}"#);
+    }
+
+    #[test]
+    fn test_command_json() {
+        let json = serde_json::to_string(&Command::Stop { wait: true }).unwrap();
+        assert_eq!(json, r#"{"command":"stop","wait":true}"#);
+        let json = serde_json::to_string(&Command::Status).unwrap();
+        assert_eq!(json, r#"{"command":"status"}"#);
+    }
+
+    #[test]
+    fn test_socket_roundtrip() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("hammertalk.sock");
+        let (tx, rx) = mpsc::channel::<ControlRequest>();
+        spawn_listener(&path, tx).unwrap();
+
+        thread::spawn(move || {
+            for req in rx {
+                match req.command {
+                    Command::Start => req.respond(Reply::ok(DaemonState::Recording)),
+                    _ => req.respond(Reply::error(DaemonState::Idle, "nope")),
+                }
+            }
+        });
+
+        let reply = send_command_to(&path, &Command::Start).unwrap();
+        assert_eq!(reply, Reply::ok(DaemonState::Recording));
+
+        let reply = send_command_to(&path, &Command::Stop { wait: false }).unwrap();
+        assert!(!reply.ok);
+        assert_eq!(reply.error.as_deref(), Some("nope"));
+    }
+
+    #[test]
+    fn test_socket_invalid_command() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("hammertalk.sock");
+        let (tx, _rx) = mpsc::channel::<ControlRequest>();
+        spawn_listener(&path, tx).unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "not json").unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+        let reply: Reply = serde_json::from_str(&line).unwrap();
+        assert!(!reply.ok);
+        assert!(reply.error.unwrap().starts_with("invalid command"));
+    }
+
+    #[test]
+    fn test_send_command_no_daemon() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("hammertalk.sock");
+        assert!(send_command_to(&path, &Command::Status).is_err());
+    }
+}
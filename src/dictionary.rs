@@ -0,0 +1,313 @@
+//! Replacement dictionary applied to each transcription before it's typed, from
+//! `dictionary.toml` next to the config file.
+//!
+//! ```toml
+//! # Spell these words like this, whatever case the engine used
+//! vocabulary = ["Hammertalk", "PipeWire"]
+//!
+//! # Case-insensitive whole-word phrases
+//! [replace]
+//! "hammer talk" = "Hammertalk"
+//! "kube cuttle" = "kubectl"
+//!
+//! # Regular expressions, applied in order after the phrases
+//! [[regex]]
+//! pattern = '(\d+) percent'
+//! replacement = '$1%'
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use log::{info, warn};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+struct DictionaryFile {
+    #[serde(default)]
+    vocabulary: Vec<String>,
+    #[serde(default)]
+    replace: BTreeMap<String, String>,
+    #[serde(default)]
+    regex: Vec<RegexRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegexRule {
+    pattern: String,
+    replacement: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    /// Every phrase in one alternation, longest first, one capture group per phrase.
+    phrases: Option<Regex>,
+    /// Replacement for each capture group of `phrases`, in order.
+    replacements: Vec<String>,
+    rules: Vec<(Regex, String)>,
+}
+
+impl Dictionary {
+    /// Load `path`. A missing file is an empty dictionary; a broken one is logged and
+    /// ignored, as are rules that don't compile.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(contents) => match Self::parse(&contents) {
+                Ok(dictionary) => {
+                    info!(
+                        "Loaded {} dictionary entries from {:?}",
+                        dictionary.len(),
+                        path
+                    );
+                    dictionary
+                }
+                Err(e) => {
+                    warn!("Failed to parse dictionary {:?}: {}", path, e);
+                    Dictionary::default()
+                }
+            },
+            Err(_) => Dictionary::default(),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        let file: DictionaryFile = toml::from_str(contents)?;
+
+        let mut phrases: Vec<(String, String)> = file
+            .vocabulary
+            .into_iter()
+            .map(|word| (word.clone(), word))
+            .chain(file.replace)
+            .filter(|(phrase, _)| !phrase.trim().is_empty())
+            .collect();
+        // Longest first, so "kube cuttle apply" wins over "kube cuttle"
+        phrases.sort_by_key(|(phrase, _)| std::cmp::Reverse(phrase.len()));
+
+        let pattern = phrases
+            .iter()
+            .map(|(phrase, _)| format!("({})", phrase_pattern(phrase)))
+            .collect::<Vec<_>>()
+            .join("|");
+        let phrase_regex = if phrases.is_empty() {
+            None
+        } else {
+            // Escaped, so only size limits can stop it compiling
+            match Regex::new(&format!("(?i){}", pattern)) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    warn!(
+                        "Skipping all {} dictionary phrases, they don't fit in one regex: {}",
+                        phrases.len(),
+                        e
+                    );
+                    phrases.clear();
+                    None
+                }
+            }
+        };
+
+        let rules = file
+            .regex
+            .into_iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(regex) => Some((regex, rule.replacement)),
+                Err(e) => {
+                    warn!("Skipping dictionary regex '{}': {}", rule.pattern, e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Dictionary {
+            phrases: phrase_regex,
+            replacements: phrases.into_iter().map(|(_, r)| r).collect(),
+            rules,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.replacements.len() + self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Apply the phrases, then each regex in turn.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = match self.phrases {
+            Some(ref phrases) => phrases
+                .replace_all(text, |caps: &Captures| {
+                    let group = (1..caps.len())
+                        .find(|&i| caps.get(i).is_some())
+                        .expect("one phrase matched");
+                    self.replacements[group - 1].clone()
+                })
+                .into_owned(),
+            None => text.to_string(),
+        };
+        for (regex, replacement) in &self.rules {
+            text = regex.replace_all(&text, replacement.as_str()).into_owned();
+        }
+        text
+    }
+}
+
+/// Regex for `phrase` as whole words, with any run of whitespace between them.
+fn phrase_pattern(phrase: &str) -> String {
+    let words: Vec<String> = phrase.split_whitespace().map(regex::escape).collect();
+    let mut pattern = words.join(r"\s+");
+    // \b only means "whole word" next to a word character
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    if is_word(phrase.trim().chars().next()) {
+        pattern.insert_str(0, r"\b");
+    }
+    if is_word(phrase.trim().chars().last()) {
+        pattern.push_str(r"\b");
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn dictionary(contents: &str) -> Dictionary {
+        Dictionary::parse(contents).unwrap()
+    }
+
+    #[test]
+    fn test_phrases_ignore_case() {
+        let dict = dictionary("[replace]\n\"hammer talk\" = \"Hammertalk\"\n");
+        assert_eq!(
+            dict.apply("I use Hammer Talk daily"),
+            "I use Hammertalk daily"
+        );
+        assert_eq!(dict.apply("HAMMER TALK."), "Hammertalk.");
+    }
+
+    #[test]
+    fn test_phrases_match_whole_words() {
+        let dict = dictionary("[replace]\n\"cat\" = \"dog\"\n");
+        assert_eq!(
+            dict.apply("cat, concatenate, cats"),
+            "dog, concatenate, cats"
+        );
+    }
+
+    #[test]
+    fn test_phrases_match_any_whitespace() {
+        let dict = dictionary("[replace]\n\"kube cuttle\" = \"kubectl\"\n");
+        assert_eq!(
+            dict.apply("run kube  cuttle get pods"),
+            "run kubectl get pods"
+        );
+        assert_eq!(dict.apply("kube\ncuttle"), "kubectl");
+    }
+
+    #[test]
+    fn test_longest_phrase_wins() {
+        let dict = dictionary(
+            "[replace]\n\"kube\" = \"cube\"\n\"kube cuttle\" = \"kubectl\"\n\"kube cuttle apply\" = \"kubectl apply -f\"\n",
+        );
+        assert_eq!(dict.apply("kube cuttle apply"), "kubectl apply -f");
+        assert_eq!(dict.apply("kube cuttle logs"), "kubectl logs");
+        assert_eq!(dict.apply("a kube"), "a cube");
+    }
+
+    #[test]
+    fn test_replacements_are_not_replaced_again() {
+        let dict = dictionary("[replace]\n\"a\" = \"b\"\n\"b\" = \"c\"\n");
+        assert_eq!(dict.apply("a b"), "b c");
+    }
+
+    #[test]
+    fn test_replacement_is_literal() {
+        let dict = dictionary("[replace]\n\"dollar one\" = \"$1\"\n");
+        assert_eq!(dict.apply("costs dollar one"), "costs $1");
+    }
+
+    #[test]
+    fn test_phrase_with_punctuation() {
+        let dict = dictionary("[replace]\n\"c++\" = \"C++\"\n\"node.js\" = \"Node.js\"\n");
+        assert_eq!(dict.apply("c++ and node.js"), "C++ and Node.js");
+        // The dot is literal
+        assert_eq!(dict.apply("nodexjs"), "nodexjs");
+    }
+
+    #[test]
+    fn test_vocabulary_fixes_case() {
+        let dict = dictionary("vocabulary = [\"PipeWire\", \"kubectl\"]\n");
+        assert_eq!(
+            dict.apply("restart pipewire then Kubectl"),
+            "restart PipeWire then kubectl"
+        );
+    }
+
+    #[test]
+    fn test_regex_rules_in_order() {
+        let dict = dictionary(
+            "[[regex]]\npattern = '(\\d+) percent'\nreplacement = '$1%'\n\n\
+             [[regex]]\npattern = '(\\d+)%'\nreplacement = '${1} pct'\n",
+        );
+        assert_eq!(dict.apply("about 50 percent done"), "about 50 pct done");
+    }
+
+    #[test]
+    fn test_regex_runs_after_phrases() {
+        let dict = dictionary(
+            "[replace]\n\"hammer talk\" = \"Hammertalk\"\n\n\
+             [[regex]]\npattern = '^Hammertalk'\nreplacement = 'HT'\n",
+        );
+        assert_eq!(dict.apply("hammer talk rocks"), "HT rocks");
+    }
+
+    #[test]
+    fn test_bad_regex_is_skipped() {
+        let dict = dictionary(
+            "[[regex]]\npattern = '(unclosed'\nreplacement = 'x'\n\n\
+             [[regex]]\npattern = 'fine'\nreplacement = 'good'\n",
+        );
+        assert_eq!(dict.len(), 1);
+        assert_eq!(dict.apply("fine"), "good");
+    }
+
+    #[test]
+    fn test_phrases_too_big_for_a_regex_are_skipped() {
+        // Case-insensitive Unicode word boundaries make each phrase costly to compile
+        let mut contents =
+            String::from("[[regex]]\npattern = 'fine'\nreplacement = 'good'\n\n[replace]\n");
+        for i in 0..5_000 {
+            contents.push_str(&format!("\"phrase number {} here\" = \"x\"\n", i));
+        }
+        let dict = dictionary(&contents);
+        assert_eq!(dict.len(), 1);
+        assert_eq!(
+            dict.apply("phrase number 7 here is fine"),
+            "phrase number 7 here is good"
+        );
+    }
+
+    #[test]
+    fn test_empty_dictionary() {
+        let dict = dictionary("");
+        assert!(dict.is_empty());
+        assert_eq!(dict.apply("unchanged Text"), "unchanged Text");
+    }
+
+    #[test]
+    fn test_load() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("dictionary.toml");
+        assert!(Dictionary::load(&path).is_empty());
+
+        fs::write(&path, "[replace]\n\"hammer talk\" = \"Hammertalk\"\n").unwrap();
+        assert_eq!(Dictionary::load(&path).apply("hammer talk"), "Hammertalk");
+
+        fs::write(&path, "[replace\nbroken").unwrap();
+        assert!(Dictionary::load(&path).is_empty());
+    }
+}
@@ -1,84 +1,598 @@
+//! Speech-to-text backends. Each implements [`Transcriber`]; [`for_choice`] builds the
+//! one for an [`EngineChoice`].
+//!
+//! A backend of your own is added with [`register`], before the settings are read. Its
+//! name then parses as [`EngineChoice::Custom`], so `--engine`, the config file and
+//! `ctl engine` can select it, and it loads like a built-in one.
+
+use std::collections::BTreeMap;
+use std::error::Error;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 
-use log::info;
+use log::{info, warn};
 use transcribe_rs::engines::moonshine::{ModelVariant, MoonshineEngine, MoonshineModelParams};
 use transcribe_rs::engines::parakeet::{ParakeetEngine, ParakeetModelParams};
 use transcribe_rs::engines::whisper::{WhisperEngine, WhisperInferenceParams, WhisperModelParams};
 use transcribe_rs::{TranscriptionEngine, TranscriptionResult};
 
+use crate::models::MODELS;
 use crate::EngineChoice;
 
-pub enum Engine {
-    Moonshine(Box<MoonshineEngine>),
-    Whisper(WhisperEngine),
-    Parakeet(ParakeetEngine),
+/// Text of one recording, with timestamped segments if the backend provides them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcription {
+    pub text: String,
+    pub segments: Vec<Segment>,
 }
 
-impl Engine {
-    pub fn new(choice: &EngineChoice) -> Self {
-        match choice {
-            EngineChoice::MoonshineTiny | EngineChoice::MoonshineBase => {
-                Engine::Moonshine(Box::new(MoonshineEngine::new()))
-            }
-            EngineChoice::WhisperTiny
-            | EngineChoice::WhisperBase
-            | EngineChoice::WhisperSmall
-            | EngineChoice::WhisperMedium
-            | EngineChoice::WhisperLargeV3
-            | EngineChoice::WhisperLargeV3Turbo => Engine::Whisper(WhisperEngine::new()),
-            EngineChoice::ParakeetTdtV3 | EngineChoice::ParakeetTdtV3Int8 => {
-                Engine::Parakeet(ParakeetEngine::new())
-            }
+/// A stretch of the recording and its text, in seconds from the start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+}
+
+impl From<TranscriptionResult> for Transcription {
+    fn from(result: TranscriptionResult) -> Self {
+        Transcription {
+            text: result.text,
+            segments: result
+                .segments
+                .unwrap_or_default()
+                .into_iter()
+                .map(|s| Segment {
+                    start: s.start,
+                    end: s.end,
+                    text: s.text,
+                })
+                .collect(),
         }
     }
+}
+
+/// Languages the multilingual Whisper models know, by the codes Whisper uses.
+pub const WHISPER_LANGUAGES: &[&str] = &[
+    "af", "am", "ar", "as", "az", "ba", "be", "bg", "bn", "bo", "br", "bs", "ca", "cs", "cy", "da",
+    "de", "el", "en", "es", "et", "eu", "fa", "fi", "fo", "fr", "gl", "gu", "ha", "haw", "he",
+    "hi", "hr", "ht", "hu", "hy", "id", "is", "it", "ja", "jw", "ka", "kk", "km", "kn", "ko", "la",
+    "lb", "ln", "lo", "lt", "lv", "mg", "mi", "mk", "ml", "mn", "mr", "ms", "mt", "my", "ne", "nl",
+    "nn", "no", "oc", "pa", "pl", "ps", "pt", "ro", "ru", "sa", "sd", "si", "sk", "sl", "sn", "so",
+    "sq", "sr", "su", "sv", "sw", "ta", "te", "tg", "th", "tk", "tl", "tr", "tt", "uk", "ur", "uz",
+    "vi", "yi", "yo", "yue", "zh",
+];
+
+/// The 25 European languages Parakeet TDT v3 was trained on.
+pub const PARAKEET_LANGUAGES: &[&str] = &[
+    "bg", "cs", "da", "de", "el", "en", "es", "et", "fi", "fr", "hr", "hu", "it", "lt", "lv", "mt",
+    "nl", "pl", "pt", "ro", "ru", "sk", "sl", "sv", "uk",
+];
+
+const ENGLISH: &[&str] = &["en"];
+
+/// What a backend can do beyond turning speech into text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Honours the `language` passed to `transcribe`.
+    pub language: bool,
+    /// Languages the model can transcribe.
+    pub languages: &'static [&'static str],
+    /// Works out which of its languages is spoken when no language is given.
+    pub auto_detect: bool,
+    /// Can translate what it hears into English.
+    pub translate: bool,
+    /// Fills in `Transcription::segments`.
+    pub timestamps: bool,
+}
+
+impl Capabilities {
+    pub fn supports(&self, language: &str) -> bool {
+        self.languages.contains(&language)
+    }
+}
 
-    pub fn load(
+/// Decoding settings passed to every Whisper call, from the `[whisper]` config table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhisperOptions {
+    /// Text the model treats as what came before, to steer spelling of names and jargon.
+    pub initial_prompt: Option<String>,
+    /// Translate speech into English instead of transcribing it.
+    pub translate: bool,
+    pub suppress_blank: bool,
+    /// Drop tokens like `[BLANK_AUDIO]` and `(music)`.
+    pub suppress_non_speech: bool,
+    /// How sure the model must be that there's no speech to output nothing, 0.0 to 1.0.
+    pub no_speech_threshold: f32,
+}
+
+impl Default for WhisperOptions {
+    fn default() -> Self {
+        let defaults = WhisperInferenceParams::default();
+        WhisperOptions {
+            initial_prompt: defaults.initial_prompt,
+            translate: defaults.translate,
+            suppress_blank: defaults.suppress_blank,
+            suppress_non_speech: defaults.suppress_non_speech_tokens,
+            no_speech_threshold: defaults.no_speech_thold,
+        }
+    }
+}
+
+impl WhisperOptions {
+    fn params(&self, language: Option<&str>) -> WhisperInferenceParams {
+        WhisperInferenceParams {
+            language: language.map(|s| s.to_string()),
+            translate: self.translate,
+            suppress_blank: self.suppress_blank,
+            suppress_non_speech_tokens: self.suppress_non_speech,
+            no_speech_thold: self.no_speech_threshold,
+            initial_prompt: self.initial_prompt.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A speech-to-text backend. Implement this and [`register`] it, or hand it straight to
+/// a [`Worker`](crate::worker::Worker), to use an engine of your own.
+pub trait Transcriber: Send {
+    /// Load the model from `path`, which is a file or directory depending on the backend.
+    fn load(&mut self, path: &Path) -> Result<(), Box<dyn Error>>;
+
+    /// Transcribe mono `SAMPLE_RATE` audio.
+    fn transcribe(
         &mut self,
-        choice: &EngineChoice,
-        path: &Path,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        info!("Loading {} engine from {:?}", choice, path);
-        match self {
-            Engine::Moonshine(engine) => {
-                let variant = match choice {
-                    EngineChoice::MoonshineBase => ModelVariant::Base,
-                    _ => ModelVariant::Tiny,
-                };
-                engine.load_model_with_params(path, MoonshineModelParams::variant(variant))?;
-            }
-            Engine::Whisper(engine) => {
-                engine.load_model_with_params(path, WhisperModelParams::default())?;
+        samples: Vec<f32>,
+        language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error>>;
+
+    fn capabilities(&self) -> Capabilities;
+}
+
+/// Builds a backend added with [`register`], not loaded yet.
+pub type Factory = Arc<dyn Fn() -> Box<dyn Transcriber> + Send + Sync>;
+
+/// Backends added with [`register`], by name.
+static REGISTERED: Mutex<BTreeMap<String, Factory>> = Mutex::new(BTreeMap::new());
+
+/// Add a backend under `name`, which then parses as [`EngineChoice::Custom`]. Names
+/// are matched like the built-in ones, ignoring case and with `_` for `-`. Fails if a
+/// built-in engine already has the name; registering a name again replaces it.
+pub fn register(
+    name: &str,
+    factory: impl Fn() -> Box<dyn Transcriber> + Send + Sync + 'static,
+) -> Result<(), String> {
+    let name = normalize_name(name);
+    if name.is_empty() {
+        return Err("engine name is empty".to_string());
+    }
+    if let Ok(choice) = name.parse::<EngineChoice>() {
+        if !matches!(choice, EngineChoice::Custom(_)) {
+            return Err(format!("{} is a built-in engine", name));
+        }
+    }
+    REGISTERED.lock().unwrap().insert(name, Arc::new(factory));
+    Ok(())
+}
+
+/// Names of the backends added with [`register`].
+pub fn registered() -> Vec<String> {
+    REGISTERED.lock().unwrap().keys().cloned().collect()
+}
+
+/// The factory registered under `name`, already normalized.
+pub(crate) fn factory(name: &str) -> Option<Factory> {
+    REGISTERED.lock().unwrap().get(name).cloned()
+}
+
+/// How engine names are compared.
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase().replace('_', "-")
+}
+
+/// The backend for `choice`, not loaded yet.
+pub fn for_choice(choice: &EngineChoice) -> Box<dyn Transcriber> {
+    for_choice_with(choice, &WhisperOptions::default())
+}
+
+/// Like `for_choice`, with `whisper` used by the Whisper engines.
+pub fn for_choice_with(choice: &EngineChoice, whisper: &WhisperOptions) -> Box<dyn Transcriber> {
+    match choice {
+        EngineChoice::MoonshineTiny => Box::new(Moonshine::new(ModelVariant::Tiny)),
+        EngineChoice::MoonshineBase => Box::new(Moonshine::new(ModelVariant::Base)),
+        EngineChoice::WhisperTinyEn
+        | EngineChoice::WhisperBaseEn
+        | EngineChoice::WhisperSmallEn
+        | EngineChoice::WhisperMediumEn => {
+            Box::new(Whisper::english_only().with_options(whisper.clone()))
+        }
+        EngineChoice::WhisperTiny
+        | EngineChoice::WhisperBase
+        | EngineChoice::WhisperSmall
+        | EngineChoice::WhisperMedium
+        | EngineChoice::WhisperLargeV3
+        | EngineChoice::WhisperLargeV3Turbo => {
+            Box::new(Whisper::new().with_options(whisper.clone()))
+        }
+        EngineChoice::ParakeetTdtV3 | EngineChoice::ParakeetTdtV3Int8 => Box::new(Parakeet::new()),
+        EngineChoice::Custom(name) => match factory(name) {
+            Some(factory) => factory(),
+            None => Box::new(Unregistered(name.clone())),
+        },
+    }
+}
+
+/// Stands in for a custom engine that was never registered, so loading it fails with
+/// an error instead of a panic.
+struct Unregistered(String);
+
+impl Transcriber for Unregistered {
+    fn load(&mut self, _path: &Path) -> Result<(), Box<dyn Error>> {
+        Err(format!("no engine is registered as {}", self.0).into())
+    }
+
+    fn transcribe(
+        &mut self,
+        _samples: Vec<f32>,
+        _language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error>> {
+        Err(format!("no engine is registered as {}", self.0).into())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// Check the configured language against what `choice` can do. Fails when the engine
+/// can't transcribe it, naming engines that can; logs when the setting has no effect.
+/// `language` is None for auto-detect.
+pub fn check_language(choice: &EngineChoice, language: Option<&str>) -> Result<(), String> {
+    let caps = for_choice(choice).capabilities();
+    match language {
+        Some(lang) if !caps.supports(lang) => {
+            if let Some(multilingual) = choice.multilingual() {
+                return Err(format!(
+                    "{} only transcribes English, not '{}'. Use {} instead",
+                    choice, lang, multilingual
+                ));
             }
-            Engine::Parakeet(engine) => {
-                // Auto-detect quantization: use int8 if int8 model files exist, else fp32
-                let int8_encoder = path.join("encoder-model.int8.onnx");
-                let params = if int8_encoder.exists() {
-                    info!("Detected int8 quantized Parakeet model");
-                    ParakeetModelParams::int8()
-                } else {
-                    ParakeetModelParams::fp32()
-                };
-                engine.load_model_with_params(path, params)?;
+            let capable: Vec<String> = MODELS
+                .iter()
+                .filter(|m| for_choice(&m.engine).capabilities().supports(lang))
+                .map(|m| m.engine.to_string())
+                .collect();
+            if capable.is_empty() {
+                Err(format!(
+                    "no engine transcribes '{}'. Languages are two-letter codes such as 'de'",
+                    lang
+                ))
+            } else {
+                Err(format!(
+                    "{} can't transcribe '{}'. Engines that can: {}",
+                    choice,
+                    lang,
+                    capable.join(", ")
+                ))
             }
         }
-        Ok(())
+        Some(lang) if !caps.language && caps.auto_detect => {
+            info!(
+                "The {} engine detects the language itself, '{}' is only checked against the ones it knows",
+                choice, lang
+            );
+            Ok(())
+        }
+        Some(lang) if !caps.language => {
+            warn!(
+                "The {} engine ignores the language setting '{}' and only transcribes {}",
+                choice,
+                lang,
+                caps.languages.join(", ")
+            );
+            Ok(())
+        }
+        None if !caps.auto_detect => {
+            warn!(
+                "The {} engine can't detect the language and only transcribes {}",
+                choice,
+                caps.languages.join(", ")
+            );
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub struct Moonshine {
+    engine: Box<MoonshineEngine>,
+    variant: ModelVariant,
+}
+
+impl Moonshine {
+    pub fn new(variant: ModelVariant) -> Self {
+        Moonshine {
+            engine: Box::new(MoonshineEngine::new()),
+            variant,
+        }
+    }
+}
+
+impl Transcriber for Moonshine {
+    fn load(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.engine
+            .load_model_with_params(path, MoonshineModelParams::variant(self.variant))
     }
 
-    pub fn transcribe(
+    fn transcribe(
+        &mut self,
+        samples: Vec<f32>,
+        _language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error>> {
+        Ok(self.engine.transcribe_samples(samples, None)?.into())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            language: false,
+            languages: ENGLISH,
+            auto_detect: false,
+            translate: false,
+            timestamps: false,
+        }
+    }
+}
+
+pub struct Whisper {
+    engine: WhisperEngine,
+    /// Loads one of the `.en` models, which only know English.
+    english_only: bool,
+    options: WhisperOptions,
+}
+
+impl Whisper {
+    pub fn new() -> Self {
+        Whisper {
+            engine: WhisperEngine::new(),
+            english_only: false,
+            options: WhisperOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: WhisperOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn english_only() -> Self {
+        Whisper {
+            english_only: true,
+            ..Self::new()
+        }
+    }
+}
+
+impl Default for Whisper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transcriber for Whisper {
+    fn load(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.engine
+            .load_model_with_params(path, WhisperModelParams::default())
+    }
+
+    fn transcribe(
         &mut self,
         samples: Vec<f32>,
         language: Option<&str>,
-    ) -> Result<TranscriptionResult, Box<dyn std::error::Error>> {
-        match self {
-            Engine::Moonshine(engine) => Ok(engine.transcribe_samples(samples, None)?),
-            Engine::Whisper(engine) => {
-                let params = WhisperInferenceParams {
-                    language: language.map(|s| s.to_string()),
-                    ..Default::default()
-                };
-                Ok(engine.transcribe_samples(samples, Some(params))?)
-            }
-            Engine::Parakeet(engine) => Ok(engine.transcribe_samples(samples, None)?),
+    ) -> Result<Transcription, Box<dyn Error>> {
+        let params = self.options.params(language);
+        Ok(self
+            .engine
+            .transcribe_samples(samples, Some(params))?
+            .into())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            language: true,
+            languages: if self.english_only {
+                ENGLISH
+            } else {
+                WHISPER_LANGUAGES
+            },
+            auto_detect: !self.english_only,
+            translate: !self.english_only,
+            timestamps: true,
         }
     }
 }
+
+pub struct Parakeet {
+    engine: ParakeetEngine,
+}
+
+impl Parakeet {
+    pub fn new() -> Self {
+        Parakeet {
+            engine: ParakeetEngine::new(),
+        }
+    }
+}
+
+impl Default for Parakeet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transcriber for Parakeet {
+    fn load(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        // Auto-detect quantization: use int8 if int8 model files exist, else fp32
+        let int8_encoder = path.join("encoder-model.int8.onnx");
+        let params = if int8_encoder.exists() {
+            info!("Detected int8 quantized Parakeet model");
+            ParakeetModelParams::int8()
+        } else {
+            ParakeetModelParams::fp32()
+        };
+        self.engine.load_model_with_params(path, params)
+    }
+
+    fn transcribe(
+        &mut self,
+        samples: Vec<f32>,
+        _language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error>> {
+        Ok(self.engine.transcribe_samples(samples, None)?.into())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            language: false,
+            languages: PARAKEET_LANGUAGES,
+            auto_detect: true,
+            translate: false,
+            timestamps: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transcribe_rs::TranscriptionSegment;
+
+    #[test]
+    fn test_transcription_from_result() {
+        let result = TranscriptionResult {
+            text: "hello world".to_string(),
+            segments: Some(vec![TranscriptionSegment {
+                start: 0.0,
+                end: 1.5,
+                text: "hello world".to_string(),
+            }]),
+        };
+        let transcription = Transcription::from(result);
+        assert_eq!(transcription.text, "hello world");
+        assert_eq!(
+            transcription.segments,
+            vec![Segment {
+                start: 0.0,
+                end: 1.5,
+                text: "hello world".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transcription_without_segments() {
+        let result = TranscriptionResult {
+            text: "hi".to_string(),
+            segments: None,
+        };
+        assert!(Transcription::from(result).segments.is_empty());
+    }
+
+    #[test]
+    fn test_capabilities() {
+        assert!(
+            for_choice(&EngineChoice::WhisperBase)
+                .capabilities()
+                .language
+        );
+        assert!(
+            !for_choice(&EngineChoice::MoonshineTiny)
+                .capabilities()
+                .language
+        );
+        assert!(
+            !for_choice(&EngineChoice::ParakeetTdtV3)
+                .capabilities()
+                .language
+        );
+        assert!(
+            for_choice(&EngineChoice::ParakeetTdtV3)
+                .capabilities()
+                .timestamps
+        );
+        assert!(
+            !for_choice(&EngineChoice::MoonshineBase)
+                .capabilities()
+                .timestamps
+        );
+    }
+
+    #[test]
+    fn test_language_tables() {
+        let whisper = for_choice(&EngineChoice::WhisperSmall).capabilities();
+        assert!(whisper.supports("de") && whisper.auto_detect && whisper.translate);
+        let english = for_choice(&EngineChoice::WhisperSmallEn).capabilities();
+        assert!(!english.supports("de") && !english.auto_detect && !english.translate);
+        let parakeet = for_choice(&EngineChoice::ParakeetTdtV3Int8).capabilities();
+        assert!(parakeet.supports("fr") && !parakeet.supports("ja") && parakeet.auto_detect);
+        let moonshine = for_choice(&EngineChoice::MoonshineTiny).capabilities();
+        assert_eq!(moonshine.languages, ["en"]);
+    }
+
+    #[test]
+    fn test_check_language() {
+        assert!(check_language(&EngineChoice::WhisperSmallEn, Some("en")).is_ok());
+        assert!(check_language(&EngineChoice::WhisperSmallEn, None).is_ok());
+        let err = check_language(&EngineChoice::WhisperSmallEn, Some("de")).unwrap_err();
+        assert!(err.contains("whisper-small-en"));
+        assert!(err.contains("Use whisper-small instead"));
+
+        assert!(check_language(&EngineChoice::WhisperSmall, Some("de")).is_ok());
+        assert!(check_language(&EngineChoice::WhisperLargeV3, Some("es")).is_ok());
+        // Parakeet picks the language itself, but only among the ones it knows
+        assert!(check_language(&EngineChoice::ParakeetTdtV3, Some("fr")).is_ok());
+    }
+
+    #[test]
+    fn test_check_language_names_capable_engines() {
+        let err = check_language(&EngineChoice::MoonshineBase, Some("fr")).unwrap_err();
+        assert!(
+            err.contains("moonshine-base can't transcribe 'fr'"),
+            "{}",
+            err
+        );
+        assert!(err.contains("whisper-large-v3"), "{}", err);
+        assert!(err.contains("parakeet-tdt-v3"), "{}", err);
+        assert!(!err.contains("whisper-tiny-en"), "{}", err);
+
+        let err = check_language(&EngineChoice::ParakeetTdtV3, Some("ja")).unwrap_err();
+        assert!(!err.contains("parakeet-tdt-v3-int8"), "{}", err);
+
+        let err = check_language(&EngineChoice::WhisperBase, Some("german")).unwrap_err();
+        assert!(err.contains("no engine transcribes 'german'"), "{}", err);
+    }
+
+    #[test]
+    fn test_whisper_options_passed_through() {
+        let options = WhisperOptions {
+            initial_prompt: Some("Hammertalk, Waybar".to_string()),
+            translate: true,
+            suppress_blank: false,
+            suppress_non_speech: false,
+            no_speech_threshold: 0.6,
+        };
+        let params = options.params(Some("de"));
+        assert_eq!(params.language.as_deref(), Some("de"));
+        assert_eq!(params.initial_prompt.as_deref(), Some("Hammertalk, Waybar"));
+        assert!(params.translate);
+        assert!(!params.suppress_blank);
+        assert!(!params.suppress_non_speech_tokens);
+        assert_eq!(params.no_speech_thold, 0.6);
+
+        // The defaults leave the backend's own untouched
+        let params = WhisperOptions::default().params(None);
+        let defaults = WhisperInferenceParams::default();
+        assert_eq!(params.suppress_blank, defaults.suppress_blank);
+        assert_eq!(params.no_speech_thold, defaults.no_speech_thold);
+        assert_eq!(params.initial_prompt, None);
+    }
+}
@@ -1,44 +1,54 @@
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
 use std::sync::Arc;
 
 use handy_keys::{Hotkey, HotkeyManager, HotkeyState};
 use log::{error, info, warn};
 
+use crate::control::{Command, ControlRequest};
 use crate::load_config;
 
-/// Parse the hotkey from CLI args, env var, config file, or platform default.
-/// Priority: --hotkey flag > HAMMERTALK_HOTKEY env > config file > platform default.
-/// On macOS the default is "Fn" (globe key). On Linux there is no default
-/// (users bind keys in their compositor via hammertalk-ctl).
-/// Pass --hotkey none to disable the hotkey.
-pub fn parse_hotkey_arg() -> Option<String> {
-    let args: Vec<String> = std::env::args().collect();
+/// Look up a hotkey setting. Priority: CLI flag > env var > config file.
+/// Returns `Some(None)` when explicitly disabled with "none", `None` when unset.
+fn lookup_hotkey(
+    flag: &str,
+    env_var: &str,
+    config_value: impl FnOnce() -> Option<String>,
+) -> Option<Option<String>> {
+    let disabled = |val: String| {
+        if val.eq_ignore_ascii_case("none") {
+            None
+        } else {
+            Some(val)
+        }
+    };
 
     // CLI arg takes priority
-    if let Some(pos) = args.iter().position(|a| a == "--hotkey") {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|a| a == flag) {
         if let Some(val) = args.get(pos + 1) {
-            if val.eq_ignore_ascii_case("none") {
-                return None;
-            }
-            return Some(val.clone());
+            return Some(disabled(val.clone()));
         }
     }
 
     // Env var
-    if let Ok(val) = std::env::var("HAMMERTALK_HOTKEY") {
-        if val.eq_ignore_ascii_case("none") {
-            return None;
-        }
-        return Some(val);
+    if let Ok(val) = std::env::var(env_var) {
+        return Some(disabled(val));
     }
 
     // Config file
-    let config = load_config();
-    if let Some(hotkey) = config.hotkey {
-        if hotkey.eq_ignore_ascii_case("none") {
-            return None;
-        }
-        return Some(hotkey);
+    config_value().map(disabled)
+}
+
+/// Parse the hotkey from CLI args, env var, config file, or platform default.
+/// Priority: --hotkey flag > HAMMERTALK_HOTKEY env > config file > platform default.
+/// On macOS the default is "Fn" (globe key). On Linux there is no default
+/// (users bind keys in their compositor via hammertalk-ctl).
+/// Pass --hotkey none to disable the hotkey.
+pub fn parse_hotkey_arg() -> Option<String> {
+    if let Some(hotkey) = lookup_hotkey("--hotkey", "HAMMERTALK_HOTKEY", || load_config().hotkey) {
+        return hotkey;
     }
 
     // Platform default: Fn on macOS, no default on Linux
@@ -49,6 +59,68 @@ pub fn parse_hotkey_arg() -> Option<String> {
     }
 }
 
+/// Parse the cancel hotkey, which discards the current recording without transcribing.
+/// Priority: --cancel-hotkey flag > HAMMERTALK_CANCEL_HOTKEY env > config file.
+/// There is no default.
+pub fn parse_cancel_hotkey_arg() -> Option<String> {
+    lookup_hotkey("--cancel-hotkey", "HAMMERTALK_CANCEL_HOTKEY", || {
+        load_config().cancel_hotkey
+    })
+    .flatten()
+}
+
+/// How the push-to-talk hotkey drives recording.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HotkeyMode {
+    /// Hold the key to record, release to transcribe.
+    #[default]
+    Hold,
+    /// Press once to start recording, press again to transcribe.
+    Toggle,
+}
+
+impl FromStr for HotkeyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hold" => Ok(HotkeyMode::Hold),
+            "toggle" => Ok(HotkeyMode::Toggle),
+            _ => Err(format!("unknown hotkey mode: {}", s)),
+        }
+    }
+}
+
+/// Parse the hotkey mode. Priority: --hotkey-mode flag > HAMMERTALK_HOTKEY_MODE env >
+/// config file > hold.
+pub fn parse_hotkey_mode() -> HotkeyMode {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|a| a == "--hotkey-mode") {
+        if let Some(val) = args.get(pos + 1) {
+            match val.parse() {
+                Ok(mode) => return mode,
+                Err(_) => warn!("Unknown hotkey mode '{}', using hold", val),
+            }
+        }
+    }
+
+    if let Ok(val) = std::env::var("HAMMERTALK_HOTKEY_MODE") {
+        match val.parse() {
+            Ok(mode) => return mode,
+            Err(_) => warn!("Unknown HAMMERTALK_HOTKEY_MODE '{}', using hold", val),
+        }
+    }
+
+    if let Some(val) = load_config().hotkey_mode {
+        match val.parse() {
+            Ok(mode) => return mode,
+            Err(_) => warn!("Unknown hotkey_mode '{}' in config file, using hold", val),
+        }
+    }
+
+    HotkeyMode::Hold
+}
+
 /// Check accessibility permissions (macOS) and log a helpful message if missing.
 pub fn check_permissions() -> bool {
     #[cfg(target_os = "macos")]
@@ -65,16 +137,29 @@ pub fn check_permissions() -> bool {
     true
 }
 
-/// Start a global hotkey listener that sends SIGUSR1 on press and SIGUSR2 on release.
-/// This runs in the current thread and blocks forever.
-pub fn run_hotkey_listener(hotkey_str: &str, running: Arc<AtomicBool>) {
-    let hotkey: Hotkey = match hotkey_str.parse() {
-        Ok(h) => h,
+fn parse_hotkey(hotkey_str: &str) -> Option<Hotkey> {
+    match hotkey_str.parse() {
+        Ok(h) => Some(h),
         Err(e) => {
             error!("Invalid hotkey '{}': {}", hotkey_str, e);
             error!("Examples: Cmd+Shift+T, Ctrl+Alt+Space, F18");
-            return;
+            None
         }
+    }
+}
+
+/// Start a global hotkey listener. In hold mode it sends `Start` on press and `Stop`
+/// on release; in toggle mode every press sends `Toggle`. The optional cancel hotkey
+/// sends `Cancel`. This runs in the current thread and blocks forever.
+pub fn run_hotkey_listener(
+    hotkey_str: &str,
+    mode: HotkeyMode,
+    cancel_hotkey_str: Option<&str>,
+    commands: Sender<ControlRequest>,
+    running: Arc<AtomicBool>,
+) {
+    let Some(hotkey) = parse_hotkey(hotkey_str) else {
+        return;
     };
 
     let manager = match HotkeyManager::new() {
@@ -94,30 +179,53 @@ pub fn run_hotkey_listener(hotkey_str: &str, running: Arc<AtomicBool>) {
     };
 
     info!("Global hotkey registered: {} (id={:?})", hotkey, id);
-    info!("Hold the key to record, release to transcribe.");
+    match mode {
+        HotkeyMode::Hold => info!("Hold the key to record, release to transcribe."),
+        HotkeyMode::Toggle => info!("Press the key to start recording, press again to transcribe."),
+    }
+
+    let cancel_id = cancel_hotkey_str.and_then(parse_hotkey).and_then(|cancel| {
+        match manager.register(cancel) {
+            Ok(cancel_id) => {
+                info!("Cancel hotkey registered: {} (id={:?})", cancel, cancel_id);
+                Some(cancel_id)
+            }
+            Err(e) => {
+                error!("Failed to register cancel hotkey '{}': {}", cancel, e);
+                None
+            }
+        }
+    });
 
-    let pid = std::process::id() as i32;
+    let send = |command: Command| {
+        let _ = commands.send(ControlRequest::new(command));
+    };
 
     while running.load(Ordering::SeqCst) {
         match manager.recv() {
             Ok(event) => {
                 if event.id == id {
-                    match event.state {
-                        HotkeyState::Pressed => {
+                    match (mode, event.state) {
+                        (HotkeyMode::Toggle, HotkeyState::Pressed) => {
+                            info!("Hotkey pressed → toggling recording");
+                            send(Command::Toggle { wait: false });
+                        }
+                        (HotkeyMode::Toggle, HotkeyState::Released) => {}
+                        (HotkeyMode::Hold, HotkeyState::Pressed) => {
                             info!("Hotkey pressed → starting recording");
-                            // Send SIGUSR1 to ourselves
-                            unsafe {
-                                libc::kill(pid, libc::SIGUSR1);
-                            }
+                            send(Command::Start);
                         }
-                        HotkeyState::Released => {
+                        (HotkeyMode::Hold, HotkeyState::Released) => {
                             info!("Hotkey released → stopping recording");
-                            // Send SIGUSR2 to ourselves
-                            unsafe {
-                                libc::kill(pid, libc::SIGUSR2);
-                            }
+                            send(Command::Stop { wait: false });
                         }
                     }
+                } else if Some(event.id) == cancel_id {
+                    if matches!(event.state, HotkeyState::Released) {
+                        continue;
+                    }
+                    info!("Cancel hotkey pressed → discarding recording");
+                    send(Command::Cancel);
                 }
             }
             Err(e) => {
@@ -129,3 +237,24 @@ pub fn run_hotkey_listener(hotkey_str: &str, running: Arc<AtomicBool>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hotkey_mode_from_str() {
+        assert_eq!("hold".parse::<HotkeyMode>().unwrap(), HotkeyMode::Hold);
+        assert_eq!("toggle".parse::<HotkeyMode>().unwrap(), HotkeyMode::Toggle);
+        assert_eq!(
+            " Toggle ".parse::<HotkeyMode>().unwrap(),
+            HotkeyMode::Toggle
+        );
+        assert!("press".parse::<HotkeyMode>().is_err());
+    }
+
+    #[test]
+    fn test_hotkey_mode_default() {
+        assert_eq!(HotkeyMode::default(), HotkeyMode::Hold);
+    }
+}
@@ -9,7 +9,18 @@ use std::str::FromStr;
 use log::{debug, error, info, warn};
 use serde::Deserialize;
 
+use crate::engine::WhisperOptions;
+
+pub mod audio;
+pub mod control;
+pub mod dictionary;
 pub mod engine;
+pub mod models;
+pub mod resample;
+pub mod ring;
+pub mod spoken;
+pub mod vad;
+pub mod worker;
 
 #[cfg(feature = "hotkey")]
 pub mod hotkey;
@@ -21,6 +32,12 @@ pub enum DaemonState {
     Idle,
     Recording,
     Transcribing,
+    /// Hands-free dictation: recording, and transcribing each utterance at a pause.
+    Listening,
+    /// No input device is available; waiting for one to appear.
+    Degraded,
+    /// Loading a model, at startup or to switch engines.
+    Loading,
 }
 
 impl DaemonState {
@@ -29,6 +46,9 @@ impl DaemonState {
             DaemonState::Idle => "idle",
             DaemonState::Recording => "recording",
             DaemonState::Transcribing => "transcribing",
+            DaemonState::Listening => "listening",
+            DaemonState::Degraded => "degraded",
+            DaemonState::Loading => "loading",
         }
     }
 
@@ -37,6 +57,9 @@ impl DaemonState {
             "idle" => Some(DaemonState::Idle),
             "recording" => Some(DaemonState::Recording),
             "transcribing" => Some(DaemonState::Transcribing),
+            "listening" => Some(DaemonState::Listening),
+            "degraded" => Some(DaemonState::Degraded),
+            "loading" => Some(DaemonState::Loading),
             _ => None,
         }
     }
@@ -48,53 +71,130 @@ pub const RESAMPLE_TOLERANCE: f32 = 0.001;
 /// Delay in milliseconds to allow audio buffer to drain before transcription
 pub const BUFFER_DRAIN_DELAY_MS: u64 = 50;
 
+/// How often the main thread drains the audio ring buffer while recording
+pub const DRAIN_INTERVAL_MS: u64 = 20;
+
+/// Default length of the rolling pre-roll kept while idle
+pub const DEFAULT_PREROLL_MS: u64 = 300;
+
+/// Longest utterance in continuous dictation before it is cut without a pause
+pub const MAX_SEGMENT_SECS: usize = 30;
+
+/// Default pause that ends an utterance in continuous dictation
+pub const DEFAULT_SEGMENT_PAUSE_MS: u64 = 800;
+
+/// Default cap on a single push-to-talk recording, in case the stop command is lost
+pub const DEFAULT_MAX_RECORDING_SECS: u64 = 300;
+
+/// How long shutdown waits for queued transcriptions before saving their audio instead
+pub const SHUTDOWN_TIMEOUT_SECS: u64 = 5;
+
+/// How often to check for a stalled stream or the preferred input device coming back
+pub const DEVICE_CHECK_SECS: u64 = 2;
+
+/// Seconds of audio the ring buffer between the audio callback and the main thread can hold
+pub const RING_BUFFER_SECS: usize = 2;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum EngineChoice {
     MoonshineTiny,
     MoonshineBase,
     WhisperTiny,
+    WhisperTinyEn,
     WhisperBase,
+    WhisperBaseEn,
     WhisperSmall,
+    WhisperSmallEn,
     WhisperMedium,
+    WhisperMediumEn,
     WhisperLargeV3,
     WhisperLargeV3Turbo,
     ParakeetTdtV3,
     ParakeetTdtV3Int8,
+    /// A backend added with [`engine::register`](crate::engine::register), by name.
+    Custom(String),
 }
 
 impl FromStr for EngineChoice {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().replace('_', "-").as_str() {
+        let name = engine::normalize_name(s);
+        match name.as_str() {
             "moonshine-tiny" => Ok(EngineChoice::MoonshineTiny),
             "moonshine-base" => Ok(EngineChoice::MoonshineBase),
             "whisper-tiny" => Ok(EngineChoice::WhisperTiny),
+            "whisper-tiny-en" => Ok(EngineChoice::WhisperTinyEn),
             "whisper-base" => Ok(EngineChoice::WhisperBase),
+            "whisper-base-en" => Ok(EngineChoice::WhisperBaseEn),
             "whisper-small" => Ok(EngineChoice::WhisperSmall),
+            "whisper-small-en" => Ok(EngineChoice::WhisperSmallEn),
             "whisper-medium" => Ok(EngineChoice::WhisperMedium),
+            "whisper-medium-en" => Ok(EngineChoice::WhisperMediumEn),
             "whisper-large-v3" => Ok(EngineChoice::WhisperLargeV3),
             "whisper-large-v3-turbo" => Ok(EngineChoice::WhisperLargeV3Turbo),
             "parakeet-tdt-v3" => Ok(EngineChoice::ParakeetTdtV3),
             "parakeet-tdt-v3-int8" => Ok(EngineChoice::ParakeetTdtV3Int8),
+            _ if engine::factory(&name).is_some() => Ok(EngineChoice::Custom(name)),
             _ => Err(format!("unknown engine: {}", s)),
         }
     }
 }
 
+impl EngineChoice {
+    /// Whisper models trained on English only, which turn other languages into English.
+    pub fn is_english_only(&self) -> bool {
+        matches!(
+            self,
+            EngineChoice::WhisperTinyEn
+                | EngineChoice::WhisperBaseEn
+                | EngineChoice::WhisperSmallEn
+                | EngineChoice::WhisperMediumEn
+        )
+    }
+
+    /// The English-only model of the same size, which `whisper-tiny`, `-base`, `-small`
+    /// and `-medium` used to load.
+    pub fn english_only(&self) -> Option<EngineChoice> {
+        match self {
+            EngineChoice::WhisperTiny => Some(EngineChoice::WhisperTinyEn),
+            EngineChoice::WhisperBase => Some(EngineChoice::WhisperBaseEn),
+            EngineChoice::WhisperSmall => Some(EngineChoice::WhisperSmallEn),
+            EngineChoice::WhisperMedium => Some(EngineChoice::WhisperMediumEn),
+            _ => None,
+        }
+    }
+
+    /// The multilingual model of the same size, for English-only models.
+    pub fn multilingual(&self) -> Option<EngineChoice> {
+        match self {
+            EngineChoice::WhisperTinyEn => Some(EngineChoice::WhisperTiny),
+            EngineChoice::WhisperBaseEn => Some(EngineChoice::WhisperBase),
+            EngineChoice::WhisperSmallEn => Some(EngineChoice::WhisperSmall),
+            EngineChoice::WhisperMediumEn => Some(EngineChoice::WhisperMedium),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for EngineChoice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             EngineChoice::MoonshineTiny => write!(f, "moonshine-tiny"),
             EngineChoice::MoonshineBase => write!(f, "moonshine-base"),
             EngineChoice::WhisperTiny => write!(f, "whisper-tiny"),
+            EngineChoice::WhisperTinyEn => write!(f, "whisper-tiny-en"),
             EngineChoice::WhisperBase => write!(f, "whisper-base"),
+            EngineChoice::WhisperBaseEn => write!(f, "whisper-base-en"),
             EngineChoice::WhisperSmall => write!(f, "whisper-small"),
+            EngineChoice::WhisperSmallEn => write!(f, "whisper-small-en"),
             EngineChoice::WhisperMedium => write!(f, "whisper-medium"),
+            EngineChoice::WhisperMediumEn => write!(f, "whisper-medium-en"),
             EngineChoice::WhisperLargeV3 => write!(f, "whisper-large-v3"),
             EngineChoice::WhisperLargeV3Turbo => write!(f, "whisper-large-v3-turbo"),
             EngineChoice::ParakeetTdtV3 => write!(f, "parakeet-tdt-v3"),
             EngineChoice::ParakeetTdtV3Int8 => write!(f, "parakeet-tdt-v3-int8"),
+            EngineChoice::Custom(name) => write!(f, "{}", name),
         }
     }
 }
@@ -104,6 +204,35 @@ pub struct Config {
     pub engine: Option<String>,
     pub language: Option<String>,
     pub hotkey: Option<String>,
+    pub hotkey_mode: Option<String>,
+    pub cancel_hotkey: Option<String>,
+    pub input_device: Option<String>,
+    pub lazy_mic: Option<bool>,
+    pub mic_linger_ms: Option<u64>,
+    pub preroll_ms: Option<u64>,
+    pub vad: Option<bool>,
+    pub segment_pause_ms: Option<u64>,
+    pub auto_stop_silence_ms: Option<u64>,
+    pub max_recording_secs: Option<u64>,
+    pub idle_unload_secs: Option<u64>,
+    pub model_mirror: Option<String>,
+    pub spoken_commands: Option<bool>,
+    #[serde(default)]
+    pub whisper: WhisperConfig,
+}
+
+/// The `[whisper]` table of the config file.
+#[derive(Debug, Default, Deserialize)]
+pub struct WhisperConfig {
+    pub initial_prompt: Option<String>,
+    pub translate: Option<bool>,
+    pub suppress_blank: Option<bool>,
+    pub suppress_non_speech: Option<bool>,
+    pub no_speech_threshold: Option<f32>,
+    // Accepted so they can be reported as unsupported rather than silently ignored
+    pub temperature: Option<f32>,
+    pub beam_size: Option<u32>,
+    pub best_of: Option<u32>,
 }
 
 pub fn get_config_path() -> PathBuf {
@@ -117,6 +246,11 @@ pub fn get_config_path() -> PathBuf {
         .join("hammertalk/config.toml")
 }
 
+/// Replacements applied to transcriptions before they're typed.
+pub fn get_dictionary_path() -> PathBuf {
+    get_config_path().with_file_name("dictionary.toml")
+}
+
 pub fn load_config() -> Config {
     let path = get_config_path();
     match fs::read_to_string(&path) {
@@ -132,6 +266,10 @@ pub fn load_config() -> Config {
 }
 
 pub fn parse_engine_choice() -> EngineChoice {
+    resolve_old_whisper_name(configured_engine_choice())
+}
+
+fn configured_engine_choice() -> EngineChoice {
     // Check CLI args: --engine <name>
     let args: Vec<String> = std::env::args().collect();
     if let Some(pos) = args.iter().position(|a| a == "--engine") {
@@ -163,6 +301,24 @@ pub fn parse_engine_choice() -> EngineChoice {
     EngineChoice::ParakeetTdtV3Int8
 }
 
+/// `whisper-tiny`, `-base`, `-small` and `-medium` used to name the English-only models.
+/// When that's the model installed and the multilingual one isn't, keep loading it as
+/// the `-en` engine, with a deprecation warning, instead of failing to start.
+pub fn resolve_old_whisper_name(choice: EngineChoice) -> EngineChoice {
+    let Some(english) = choice.english_only() else {
+        return choice;
+    };
+    if default_model_path(&choice).exists() || !default_model_path(&english).exists() {
+        return choice;
+    }
+    warn!(
+        "{} now means the multilingual model, which isn't installed, so the English-only model is used as before. \
+         This fallback is deprecated: set the engine to {} to keep it, or run `hammertalk models download {}`",
+        choice, english, choice
+    );
+    english
+}
+
 pub fn parse_language() -> Option<String> {
     // Check CLI args: --language <lang>
     let args: Vec<String> = std::env::args().collect();
@@ -197,6 +353,243 @@ pub fn parse_language() -> Option<String> {
 
     Some("en".to_string())
 }
+
+/// Input device to record from, matched by name (exact or substring) or index.
+/// Priority: --device flag > HAMMERTALK_DEVICE env > config file > system default (None).
+pub fn parse_input_device() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|a| a == "--device") {
+        if let Some(device) = args.get(pos + 1) {
+            return Some(device.clone());
+        }
+    }
+
+    if let Ok(val) = std::env::var("HAMMERTALK_DEVICE") {
+        if !val.trim().is_empty() {
+            return Some(val);
+        }
+    }
+
+    load_config().input_device
+}
+
+/// Audio kept from just before a recording starts, so the first syllable isn't clipped.
+/// It needs the microphone open while idle, so a lazy microphone has none.
+/// Priority: --preroll-ms flag > HAMMERTALK_PREROLL_MS env > config file > default.
+pub fn parse_preroll_ms(lazy_mic: bool) -> u64 {
+    let preroll_ms = lookup_setting(
+        "--preroll-ms",
+        "HAMMERTALK_PREROLL_MS",
+        load_config().preroll_ms,
+    );
+    match preroll_ms {
+        Some(ms) if ms > 0 && lazy_mic => {
+            warn!(
+                "Pre-roll needs the microphone open while idle, ignoring preroll_ms = {} with lazy_mic",
+                ms
+            );
+            0
+        }
+        Some(ms) => ms,
+        None if lazy_mic => 0,
+        None => DEFAULT_PREROLL_MS,
+    }
+}
+
+/// Trim silence with voice activity detection before transcribing.
+/// Priority: --no-vad flag > HAMMERTALK_VAD env > config file > on.
+pub fn parse_vad() -> bool {
+    if std::env::args().any(|a| a == "--no-vad") {
+        return false;
+    }
+
+    if let Ok(val) = std::env::var("HAMMERTALK_VAD") {
+        match parse_bool(&val) {
+            Some(vad) => return vad,
+            None => warn!("Invalid HAMMERTALK_VAD '{}', ignoring", val),
+        }
+    }
+
+    load_config().vad.unwrap_or(true)
+}
+
+/// Pause that ends an utterance in continuous dictation.
+/// Priority: --segment-pause-ms flag > HAMMERTALK_SEGMENT_PAUSE_MS env > config file > default.
+pub fn parse_segment_pause_ms() -> u64 {
+    lookup_setting(
+        "--segment-pause-ms",
+        "HAMMERTALK_SEGMENT_PAUSE_MS",
+        load_config().segment_pause_ms,
+    )
+    .unwrap_or(DEFAULT_SEGMENT_PAUSE_MS)
+}
+
+/// Silence after speech that stops a recording by itself. 0 disables it.
+/// Priority: --auto-stop-silence-ms flag > HAMMERTALK_AUTO_STOP_SILENCE_MS env > config file > 0.
+pub fn parse_auto_stop_silence_ms() -> u64 {
+    lookup_setting(
+        "--auto-stop-silence-ms",
+        "HAMMERTALK_AUTO_STOP_SILENCE_MS",
+        load_config().auto_stop_silence_ms,
+    )
+    .unwrap_or(0)
+}
+
+/// Longest a recording may run before it is stopped and transcribed. 0 disables it.
+/// Priority: --max-recording-secs flag > HAMMERTALK_MAX_RECORDING_SECS env > config file > default.
+pub fn parse_max_recording_secs() -> u64 {
+    lookup_setting(
+        "--max-recording-secs",
+        "HAMMERTALK_MAX_RECORDING_SECS",
+        load_config().max_recording_secs,
+    )
+    .unwrap_or(DEFAULT_MAX_RECORDING_SECS)
+}
+
+/// Free the model after this long without recording or transcribing. 0 keeps it loaded.
+/// Priority: --idle-unload-secs flag > HAMMERTALK_IDLE_UNLOAD_SECS env > config file > 0.
+pub fn parse_idle_unload_secs() -> u64 {
+    lookup_setting(
+        "--idle-unload-secs",
+        "HAMMERTALK_IDLE_UNLOAD_SECS",
+        load_config().idle_unload_secs,
+    )
+    .unwrap_or(0)
+}
+
+/// Parse a boolean setting such as `1`, `true`, `yes` or `off`.
+pub fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Look up a setting that takes a value: `flag <value>` on the command line, then
+/// `env_var`, then the config file. Values that don't parse are logged and skipped.
+fn lookup_setting<T: FromStr>(flag: &str, env_var: &str, config: Option<T>) -> Option<T> {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(val) = args
+        .iter()
+        .position(|a| a == flag)
+        .and_then(|pos| args.get(pos + 1))
+    {
+        match val.trim().parse() {
+            Ok(v) => return Some(v),
+            Err(_) => warn!("Invalid value '{}' for {}, ignoring", val, flag),
+        }
+    }
+
+    if let Ok(val) = std::env::var(env_var) {
+        match val.trim().parse() {
+            Ok(v) => return Some(v),
+            Err(_) => warn!("Invalid value '{}' for {}, ignoring", val, env_var),
+        }
+    }
+
+    config
+}
+
+/// Only open the microphone while recording, instead of for the daemon's whole lifetime.
+/// Priority: --lazy-mic flag > HAMMERTALK_LAZY_MIC env > config file > off.
+pub fn parse_lazy_mic() -> bool {
+    if std::env::args().any(|a| a == "--lazy-mic") {
+        return true;
+    }
+
+    if let Ok(val) = std::env::var("HAMMERTALK_LAZY_MIC") {
+        match parse_bool(&val) {
+            Some(lazy) => return lazy,
+            None => warn!("Invalid HAMMERTALK_LAZY_MIC '{}', ignoring", val),
+        }
+    }
+
+    load_config().lazy_mic.unwrap_or(false)
+}
+
+/// Turn spoken punctuation and commands like "comma" and "new line" into symbols and keys.
+/// Priority: --spoken-commands flag > HAMMERTALK_SPOKEN_COMMANDS env > config file > off.
+pub fn parse_spoken_commands() -> bool {
+    if std::env::args().any(|a| a == "--spoken-commands") {
+        return true;
+    }
+
+    if let Ok(val) = std::env::var("HAMMERTALK_SPOKEN_COMMANDS") {
+        match parse_bool(&val) {
+            Some(enabled) => return enabled,
+            None => warn!("Invalid HAMMERTALK_SPOKEN_COMMANDS '{}', ignoring", val),
+        }
+    }
+
+    load_config().spoken_commands.unwrap_or(false)
+}
+
+/// How long a lazily opened microphone stays open after a recording, so back-to-back
+/// recordings don't wait for the device to start up again.
+/// Priority: --mic-linger-ms flag > HAMMERTALK_MIC_LINGER_MS env > config file > 0.
+pub fn parse_mic_linger_ms() -> u64 {
+    lookup_setting(
+        "--mic-linger-ms",
+        "HAMMERTALK_MIC_LINGER_MS",
+        load_config().mic_linger_ms,
+    )
+    .unwrap_or(0)
+}
+
+/// Whisper decoding settings from the `[whisper]` table of the config file.
+pub fn parse_whisper_options() -> WhisperOptions {
+    let config = load_config().whisper;
+    let defaults = WhisperOptions::default();
+
+    // transcribe-rs always decodes with a 3-wide beam search and doesn't expose these
+    for (key, set) in [
+        ("temperature", config.temperature.is_some()),
+        ("beam_size", config.beam_size.is_some()),
+        ("best_of", config.best_of.is_some()),
+    ] {
+        if set {
+            warn!(
+                "[whisper] {} is not supported by the Whisper backend, ignoring",
+                key
+            );
+        }
+    }
+
+    let no_speech_threshold = match config.no_speech_threshold {
+        Some(t) if (0.0..=1.0).contains(&t) => t,
+        Some(t) => {
+            warn!(
+                "[whisper] no_speech_threshold must be between 0 and 1, ignoring {}",
+                t
+            );
+            defaults.no_speech_threshold
+        }
+        None => defaults.no_speech_threshold,
+    };
+
+    WhisperOptions {
+        initial_prompt: config.initial_prompt.filter(|p| !p.trim().is_empty()),
+        translate: config.translate.unwrap_or(defaults.translate),
+        suppress_blank: config.suppress_blank.unwrap_or(defaults.suppress_blank),
+        suppress_non_speech: config
+            .suppress_non_speech
+            .unwrap_or(defaults.suppress_non_speech),
+        no_speech_threshold,
+    }
+}
+
+/// Base URL to download models from instead of huggingface.co, e.g. a local mirror.
+/// Priority: --mirror flag > HAMMERTALK_MODEL_MIRROR env > config file > none.
+pub fn parse_model_mirror() -> Option<String> {
+    lookup_setting::<String>(
+        "--mirror",
+        "HAMMERTALK_MODEL_MIRROR",
+        load_config().model_mirror,
+    )
+    .filter(|mirror| !mirror.is_empty())
+}
+
 pub fn get_pid_path() -> PathBuf {
     std::env::var("XDG_RUNTIME_DIR")
         .map(PathBuf::from)
@@ -213,26 +606,40 @@ pub fn get_model_path(engine: &EngineChoice) -> PathBuf {
         }
     }
 
-    let base = std::env::var("XDG_DATA_HOME")
+    default_model_path(engine)
+}
+
+/// Where the models live, unless `--model-path` points elsewhere.
+pub fn get_models_dir() -> PathBuf {
+    std::env::var("XDG_DATA_HOME")
         .map(PathBuf::from)
         .unwrap_or_else(|_| {
             dirs::home_dir()
                 .unwrap_or_else(|| PathBuf::from("."))
                 .join(".local/share")
         })
-        .join("hammertalk/models");
+        .join("hammertalk/models")
+}
 
+/// Path of the model for `engine` in the models directory, ignoring `--model-path`.
+pub fn default_model_path(engine: &EngineChoice) -> PathBuf {
+    let base = get_models_dir();
     match engine {
         EngineChoice::MoonshineTiny => base.join("moonshine-tiny"),
         EngineChoice::MoonshineBase => base.join("moonshine-base"),
-        EngineChoice::WhisperTiny => base.join("ggml-tiny.en.bin"),
-        EngineChoice::WhisperBase => base.join("ggml-base.en.bin"),
-        EngineChoice::WhisperSmall => base.join("ggml-small.en.bin"),
-        EngineChoice::WhisperMedium => base.join("ggml-medium.en.bin"),
+        EngineChoice::WhisperTiny => base.join("ggml-tiny.bin"),
+        EngineChoice::WhisperTinyEn => base.join("ggml-tiny.en.bin"),
+        EngineChoice::WhisperBase => base.join("ggml-base.bin"),
+        EngineChoice::WhisperBaseEn => base.join("ggml-base.en.bin"),
+        EngineChoice::WhisperSmall => base.join("ggml-small.bin"),
+        EngineChoice::WhisperSmallEn => base.join("ggml-small.en.bin"),
+        EngineChoice::WhisperMedium => base.join("ggml-medium.bin"),
+        EngineChoice::WhisperMediumEn => base.join("ggml-medium.en.bin"),
         EngineChoice::WhisperLargeV3 => base.join("ggml-large-v3.bin"),
         EngineChoice::WhisperLargeV3Turbo => base.join("ggml-large-v3-turbo.bin"),
         EngineChoice::ParakeetTdtV3 => base.join("parakeet-tdt-v3"),
         EngineChoice::ParakeetTdtV3Int8 => base.join("parakeet-tdt-v3-int8"),
+        EngineChoice::Custom(name) => base.join(name),
     }
 }
 
@@ -251,6 +658,18 @@ pub fn remove_pid_file() {
     }
 }
 
+/// Where recordings that couldn't be transcribed before shutdown are saved.
+pub fn get_recovery_dir() -> PathBuf {
+    std::env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            dirs::home_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join(".local/state")
+        })
+        .join("hammertalk/recovered")
+}
+
 pub fn get_state_path() -> PathBuf {
     std::env::var("XDG_RUNTIME_DIR")
         .map(PathBuf::from)
@@ -281,25 +700,54 @@ pub fn remove_state_file() {
     }
 }
 
+pub fn get_socket_path() -> PathBuf {
+    std::env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/tmp"))
+        .join("hammertalk.sock")
+}
+
+pub fn remove_socket_file() {
+    let path = get_socket_path();
+    if let Err(e) = fs::remove_file(&path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove control socket: {}", e);
+        }
+    }
+}
+
+/// Removes the PID, state and socket files when dropped, including when unwinding from
+/// a panic.
+pub struct RuntimeFiles;
+
+impl Drop for RuntimeFiles {
+    fn drop(&mut self) {
+        remove_socket_file();
+        remove_state_file();
+        remove_pid_file();
+    }
+}
+
+pub fn read_pid() -> Option<u32> {
+    fs::read_to_string(get_pid_path())
+        .ok()
+        .and_then(|contents| contents.trim().parse().ok())
+}
+
 pub fn is_daemon_running() -> bool {
-    let pid_path = get_pid_path();
-    match fs::read_to_string(&pid_path) {
-        Ok(contents) => {
-            if let Ok(pid) = contents.trim().parse::<u32>() {
-                #[cfg(target_os = "macos")]
-                {
-                    // macOS has no /proc — use kill(pid, 0) to check if process exists
-                    unsafe { libc::kill(pid as i32, 0) == 0 }
-                }
-                #[cfg(not(target_os = "macos"))]
-                {
-                    PathBuf::from(format!("/proc/{}", pid)).exists()
-                }
-            } else {
-                false
+    match read_pid() {
+        Some(pid) => {
+            #[cfg(target_os = "macos")]
+            {
+                // macOS has no /proc — use kill(pid, 0) to check if process exists
+                unsafe { libc::kill(pid as i32, 0) == 0 }
+            }
+            #[cfg(not(target_os = "macos"))]
+            {
+                PathBuf::from(format!("/proc/{}", pid)).exists()
             }
         }
-        Err(_) => false,
+        None => false,
     }
 }
 
@@ -318,6 +766,24 @@ pub fn format_waybar_json(state: Option<DaemonState>) -> String {
             "transcribing",
             "Hammertalk: transcribing",
         ),
+        Some(DaemonState::Listening) => (
+            "\u{f192}",
+            "listening",
+            "listening",
+            "Hammertalk: listening",
+        ),
+        Some(DaemonState::Degraded) => (
+            "\u{f131}",
+            "degraded",
+            "degraded",
+            "Hammertalk: no input device",
+        ),
+        Some(DaemonState::Loading) => (
+            "\u{f0a30}",
+            "loading",
+            "loading",
+            "Hammertalk: loading model",
+        ),
         None => ("\u{f131}", "stopped", "stopped", "Hammertalk: stopped"),
     };
 
@@ -334,43 +800,151 @@ pub fn should_type_text(text: &str) -> bool {
     !text.trim().is_empty()
 }
 
-#[cfg(target_os = "macos")]
-pub fn type_text(text: &str) {
-    if !should_type_text(text) {
-        warn!("Empty transcription, skipping");
-        return;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Enter,
+    Backspace,
+}
+
+/// One step of typing a transcription.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Text(String),
+    Key(Key),
+}
+
+/// The text `inputs` produce, with Enter as a newline. Backspaces only remove what
+/// `inputs` typed themselves.
+pub fn render_input(inputs: &[Input]) -> String {
+    let mut text = String::new();
+    for input in inputs {
+        match input {
+            Input::Text(t) => text.push_str(t),
+            Input::Key(Key::Enter) => text.push('\n'),
+            Input::Key(Key::Backspace) => {
+                text.pop();
+            }
+        }
     }
+    text
+}
 
-    let text_with_space = format!("{} ", text);
-    info!("Typing: {}", text_with_space);
+/// How many characters `type_text` leaves on screen for `inputs`, counting the space it
+/// adds after trailing text.
+pub fn typed_len(inputs: &[Input]) -> usize {
+    let trailing_space = matches!(inputs.last(), Some(Input::Text(_)));
+    render_input(inputs).chars().count() + trailing_space as usize
+}
+
+/// Whether `inputs` type anything at all: a key press, or text that isn't just
+/// whitespace.
+pub fn is_typeable(inputs: &[Input]) -> bool {
+    inputs.iter().any(|input| match input {
+        Input::Text(text) => should_type_text(text),
+        Input::Key(_) => true,
+    })
+}
 
+/// Type `inputs` in order. A space follows trailing text, so the next transcription
+/// doesn't run into this one. Returns whether all of it was typed.
+pub fn type_text(inputs: &[Input]) -> bool {
+    if !is_typeable(inputs) {
+        return false;
+    }
+
+    info!("Typing: {:?}", render_input(inputs));
+    let last = inputs.len() - 1;
+    let mut typed = true;
+    for (i, input) in inputs.iter().enumerate() {
+        typed &= match input {
+            Input::Text(text) if i == last => type_string(&format!("{} ", text)),
+            Input::Text(text) => type_string(text),
+            Input::Key(key) => press_key(*key),
+        };
+    }
+    typed
+}
+
+#[cfg(target_os = "macos")]
+fn type_string(text: &str) -> bool {
     use enigo::{Enigo, Keyboard, Settings};
     match Enigo::new(&Settings::default()) {
-        Ok(mut enigo) => match enigo.text(&text_with_space) {
-            Ok(()) => debug!("enigo text input succeeded"),
-            Err(e) => error!("enigo text input failed: {}", e),
+        Ok(mut enigo) => match enigo.text(text) {
+            Ok(()) => {
+                debug!("enigo text input succeeded");
+                true
+            }
+            Err(e) => {
+                error!("enigo text input failed: {}", e);
+                false
+            }
         },
-        Err(e) => error!("Failed to initialize enigo: {}", e),
+        Err(e) => {
+            error!("Failed to initialize enigo: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn press_key(key: Key) -> bool {
+    use enigo::{Direction, Enigo, Keyboard, Settings};
+    let key = match key {
+        Key::Enter => enigo::Key::Return,
+        Key::Backspace => enigo::Key::Backspace,
+    };
+    match Enigo::new(&Settings::default()) {
+        Ok(mut enigo) => match enigo.key(key, Direction::Click) {
+            Ok(()) => {
+                debug!("enigo key press succeeded");
+                true
+            }
+            Err(e) => {
+                error!("enigo key press failed: {}", e);
+                false
+            }
+        },
+        Err(e) => {
+            error!("Failed to initialize enigo: {}", e);
+            false
+        }
     }
 }
 
 #[cfg(not(target_os = "macos"))]
-pub fn type_text(text: &str) {
-    if !should_type_text(text) {
-        warn!("Empty transcription, skipping");
-        return;
+fn type_string(text: &str) -> bool {
+    if text.is_empty() {
+        return true;
     }
+    run_ydotool(&["type", "-d", "0", "-H", "0", "--", text])
+}
 
-    let text_with_space = format!("{} ", text);
-    info!("Typing: {}", text_with_space);
-    let result = Command::new("ydotool")
-        .args(["type", "-d", "0", "-H", "0", "--", &text_with_space])
-        .status();
+#[cfg(not(target_os = "macos"))]
+fn press_key(key: Key) -> bool {
+    // Linux input event codes, pressed (:1) then released (:0)
+    let keys = match key {
+        Key::Enter => ["28:1", "28:0"],
+        Key::Backspace => ["14:1", "14:0"],
+    };
+    run_ydotool(&["key", "-d", "0", keys[0], keys[1]])
+}
 
-    match result {
-        Ok(status) if status.success() => debug!("ydotool succeeded"),
-        Ok(status) => warn!("ydotool exited with: {}", status),
-        Err(e) => error!("Failed to run ydotool: {}", e),
+/// Returns whether ydotool succeeded.
+#[cfg(not(target_os = "macos"))]
+fn run_ydotool(args: &[&str]) -> bool {
+    match Command::new("ydotool").args(args).status() {
+        Ok(status) if status.success() => {
+            debug!("ydotool succeeded");
+            true
+        }
+        Ok(status) => {
+            warn!("ydotool exited with: {}", status);
+            false
+        }
+        Err(e) => {
+            error!("Failed to run ydotool: {}", e);
+            false
+        }
     }
 }
 
@@ -382,6 +956,7 @@ pub fn needs_resample(source_rate: u32, target_rate: u32) -> bool {
 /// Exit with error after cleanup. Used for fatal initialization failures.
 pub fn fatal_exit(msg: &str) -> ! {
     log::error!("{}", msg);
+    remove_socket_file();
     remove_state_file();
     remove_pid_file();
     std::process::exit(1);
@@ -416,6 +991,16 @@ mod tests {
         assert_eq!(pid_path, PathBuf::from("/tmp/hammertalk.pid"));
     }
 
+    #[test]
+    #[serial]
+    fn test_get_recovery_dir_with_xdg_state_home() {
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_STATE_HOME", temp.path());
+
+        assert_eq!(get_recovery_dir(), temp.path().join("hammertalk/recovered"));
+        env::remove_var("XDG_STATE_HOME");
+    }
+
     #[test]
     #[serial]
     fn test_get_model_path_moonshine_with_xdg_data_home() {
@@ -431,6 +1016,44 @@ mod tests {
         env::remove_var("XDG_DATA_HOME");
     }
 
+    #[test]
+    #[serial]
+    fn test_old_whisper_name_falls_back_to_installed_english_model() {
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_DATA_HOME", temp.path());
+        let models = temp.path().join("hammertalk/models");
+        fs::create_dir_all(&models).unwrap();
+
+        // Nothing installed: the name means what it says
+        assert_eq!(
+            resolve_old_whisper_name(EngineChoice::WhisperSmall),
+            EngineChoice::WhisperSmall
+        );
+
+        // Only the English-only model, as installed by older versions
+        fs::write(models.join("ggml-small.en.bin"), "x").unwrap();
+        assert_eq!(
+            resolve_old_whisper_name(EngineChoice::WhisperSmall),
+            EngineChoice::WhisperSmallEn
+        );
+        assert_eq!(
+            resolve_old_whisper_name(EngineChoice::WhisperBase),
+            EngineChoice::WhisperBase
+        );
+        assert_eq!(
+            resolve_old_whisper_name(EngineChoice::WhisperLargeV3),
+            EngineChoice::WhisperLargeV3
+        );
+
+        // Once the multilingual model is there, it's used
+        fs::write(models.join("ggml-small.bin"), "x").unwrap();
+        assert_eq!(
+            resolve_old_whisper_name(EngineChoice::WhisperSmall),
+            EngineChoice::WhisperSmall
+        );
+        env::remove_var("XDG_DATA_HOME");
+    }
+
     #[test]
     #[serial]
     fn test_get_model_path_parakeet_with_xdg_data_home() {
@@ -466,7 +1089,12 @@ mod tests {
         env::set_var("XDG_DATA_HOME", temp.path());
 
         let model_path = get_model_path(&EngineChoice::WhisperTiny);
+        assert_eq!(
+            model_path,
+            temp.path().join("hammertalk/models/ggml-tiny.bin")
+        );
 
+        let model_path = get_model_path(&EngineChoice::WhisperTinyEn);
         assert_eq!(
             model_path,
             temp.path().join("hammertalk/models/ggml-tiny.en.bin")
@@ -481,7 +1109,12 @@ mod tests {
         env::set_var("XDG_DATA_HOME", temp.path());
 
         let model_path = get_model_path(&EngineChoice::WhisperBase);
+        assert_eq!(
+            model_path,
+            temp.path().join("hammertalk/models/ggml-base.bin")
+        );
 
+        let model_path = get_model_path(&EngineChoice::WhisperBaseEn);
         assert_eq!(
             model_path,
             temp.path().join("hammertalk/models/ggml-base.en.bin")
@@ -558,6 +1191,14 @@ mod tests {
             "whisper-medium".parse::<EngineChoice>().unwrap(),
             EngineChoice::WhisperMedium
         );
+        assert_eq!(
+            "whisper-small-en".parse::<EngineChoice>().unwrap(),
+            EngineChoice::WhisperSmallEn
+        );
+        assert_eq!(
+            "whisper_medium_en".parse::<EngineChoice>().unwrap(),
+            EngineChoice::WhisperMediumEn
+        );
         assert_eq!(
             "whisper-large-v3".parse::<EngineChoice>().unwrap(),
             EngineChoice::WhisperLargeV3
@@ -593,6 +1234,11 @@ mod tests {
         assert_eq!(EngineChoice::WhisperBase.to_string(), "whisper-base");
         assert_eq!(EngineChoice::WhisperSmall.to_string(), "whisper-small");
         assert_eq!(EngineChoice::WhisperMedium.to_string(), "whisper-medium");
+        assert_eq!(EngineChoice::WhisperTinyEn.to_string(), "whisper-tiny-en");
+        assert_eq!(
+            EngineChoice::WhisperMediumEn.to_string(),
+            "whisper-medium-en"
+        );
         assert_eq!(EngineChoice::WhisperLargeV3.to_string(), "whisper-large-v3");
         assert_eq!(
             EngineChoice::WhisperLargeV3Turbo.to_string(),
@@ -601,6 +1247,17 @@ mod tests {
         assert_eq!(EngineChoice::ParakeetTdtV3.to_string(), "parakeet-tdt-v3");
     }
 
+    #[test]
+    fn test_english_only_engines() {
+        assert!(EngineChoice::WhisperSmallEn.is_english_only());
+        assert!(!EngineChoice::WhisperSmall.is_english_only());
+        assert_eq!(
+            EngineChoice::WhisperTinyEn.multilingual(),
+            Some(EngineChoice::WhisperTiny)
+        );
+        assert_eq!(EngineChoice::ParakeetTdtV3.multilingual(), None);
+    }
+
     #[test]
     fn test_engine_choice_case_insensitive() {
         assert_eq!(
@@ -733,6 +1390,26 @@ mod tests {
         env::remove_var("XDG_CONFIG_HOME");
     }
 
+    #[test]
+    #[serial]
+    fn test_load_config_with_cancel_hotkey() {
+        let temp = tempdir().unwrap();
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "hotkey = \"F18\"\ncancel_hotkey = \"Escape\"\n",
+        )
+        .unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+
+        let config = load_config();
+
+        assert_eq!(config.hotkey, Some("F18".to_string()));
+        assert_eq!(config.cancel_hotkey, Some("Escape".to_string()));
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
     #[test]
     #[serial]
     fn test_load_config_missing_file() {
@@ -760,6 +1437,319 @@ mod tests {
         env::remove_var("XDG_CONFIG_HOME");
     }
 
+    #[test]
+    fn test_parse_bool() {
+        assert_eq!(parse_bool("1"), Some(true));
+        assert_eq!(parse_bool(" TRUE "), Some(true));
+        assert_eq!(parse_bool("on"), Some(true));
+        assert_eq!(parse_bool("0"), Some(false));
+        assert_eq!(parse_bool("no"), Some(false));
+        assert_eq!(parse_bool("maybe"), None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_lazy_mic() {
+        env::remove_var("HAMMERTALK_LAZY_MIC");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert!(!parse_lazy_mic());
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.toml"), "lazy_mic = true\n").unwrap();
+        assert!(parse_lazy_mic());
+
+        env::set_var("HAMMERTALK_LAZY_MIC", "0");
+        assert!(!parse_lazy_mic());
+
+        env::remove_var("HAMMERTALK_LAZY_MIC");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_spoken_commands() {
+        env::remove_var("HAMMERTALK_SPOKEN_COMMANDS");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert!(!parse_spoken_commands());
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.toml"), "spoken_commands = true\n").unwrap();
+        assert!(parse_spoken_commands());
+
+        env::set_var("HAMMERTALK_SPOKEN_COMMANDS", "off");
+        assert!(!parse_spoken_commands());
+
+        env::remove_var("HAMMERTALK_SPOKEN_COMMANDS");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_render_input() {
+        let inputs = vec![
+            Input::Text("Dear Sam,".to_string()),
+            Input::Key(Key::Enter),
+            Input::Text("hi!".to_string()),
+            Input::Key(Key::Backspace),
+        ];
+        assert_eq!(render_input(&inputs), "Dear Sam,\nhi");
+        assert_eq!(typed_len(&inputs), 12);
+        // Backspaces before any text have nothing of ours to remove
+        assert_eq!(render_input(&[Input::Key(Key::Backspace)]), "");
+    }
+
+    #[test]
+    fn test_typed_len_counts_trailing_space() {
+        assert_eq!(typed_len(&[Input::Text("héllo".to_string())]), 6);
+        assert_eq!(
+            typed_len(&[Input::Text("hi".to_string()), Input::Key(Key::Enter)]),
+            3
+        );
+        assert_eq!(typed_len(&[]), 0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_mic_linger_ms() {
+        env::remove_var("HAMMERTALK_MIC_LINGER_MS");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_mic_linger_ms(), 0);
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.toml"), "mic_linger_ms = 1500\n").unwrap();
+        assert_eq!(parse_mic_linger_ms(), 1500);
+
+        env::set_var("HAMMERTALK_MIC_LINGER_MS", "250");
+        assert_eq!(parse_mic_linger_ms(), 250);
+
+        // Invalid values fall through to the config file
+        env::set_var("HAMMERTALK_MIC_LINGER_MS", "soon");
+        assert_eq!(parse_mic_linger_ms(), 1500);
+
+        env::remove_var("HAMMERTALK_MIC_LINGER_MS");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_vad() {
+        env::remove_var("HAMMERTALK_VAD");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert!(parse_vad());
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.toml"), "vad = false\n").unwrap();
+        assert!(!parse_vad());
+
+        env::set_var("HAMMERTALK_VAD", "on");
+        assert!(parse_vad());
+
+        env::remove_var("HAMMERTALK_VAD");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_segment_pause_ms() {
+        env::remove_var("HAMMERTALK_SEGMENT_PAUSE_MS");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_segment_pause_ms(), DEFAULT_SEGMENT_PAUSE_MS);
+
+        env::set_var("HAMMERTALK_SEGMENT_PAUSE_MS", "1200");
+        assert_eq!(parse_segment_pause_ms(), 1200);
+
+        env::remove_var("HAMMERTALK_SEGMENT_PAUSE_MS");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_auto_stop_silence_ms() {
+        env::remove_var("HAMMERTALK_AUTO_STOP_SILENCE_MS");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_auto_stop_silence_ms(), 0);
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "auto_stop_silence_ms = 2000\n",
+        )
+        .unwrap();
+        assert_eq!(parse_auto_stop_silence_ms(), 2000);
+
+        env::set_var("HAMMERTALK_AUTO_STOP_SILENCE_MS", "1500");
+        assert_eq!(parse_auto_stop_silence_ms(), 1500);
+
+        env::remove_var("HAMMERTALK_AUTO_STOP_SILENCE_MS");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_max_recording_secs() {
+        env::remove_var("HAMMERTALK_MAX_RECORDING_SECS");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_max_recording_secs(), DEFAULT_MAX_RECORDING_SECS);
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.toml"), "max_recording_secs = 0\n").unwrap();
+        assert_eq!(parse_max_recording_secs(), 0);
+
+        env::set_var("HAMMERTALK_MAX_RECORDING_SECS", "bogus");
+        assert_eq!(parse_max_recording_secs(), 0);
+
+        env::remove_var("HAMMERTALK_MAX_RECORDING_SECS");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_idle_unload_secs() {
+        env::remove_var("HAMMERTALK_IDLE_UNLOAD_SECS");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_idle_unload_secs(), 0);
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.toml"), "idle_unload_secs = 600\n").unwrap();
+        assert_eq!(parse_idle_unload_secs(), 600);
+
+        env::set_var("HAMMERTALK_IDLE_UNLOAD_SECS", "60");
+        assert_eq!(parse_idle_unload_secs(), 60);
+
+        env::remove_var("HAMMERTALK_IDLE_UNLOAD_SECS");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_whisper_options() {
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_whisper_options(), WhisperOptions::default());
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "engine = \"whisper-small\"\n\n[whisper]\n\
+             initial_prompt = \"Hammertalk, Waybar, Hyprland\"\n\
+             translate = true\n\
+             suppress_blank = false\n\
+             no_speech_threshold = 0.5\n\
+             beam_size = 5\n",
+        )
+        .unwrap();
+        let options = parse_whisper_options();
+        assert_eq!(
+            options.initial_prompt.as_deref(),
+            Some("Hammertalk, Waybar, Hyprland")
+        );
+        assert!(options.translate);
+        assert!(!options.suppress_blank);
+        assert!(options.suppress_non_speech);
+        assert_eq!(options.no_speech_threshold, 0.5);
+
+        fs::write(
+            config_dir.join("config.toml"),
+            "[whisper]\ninitial_prompt = \"  \"\nno_speech_threshold = 3.0\n",
+        )
+        .unwrap();
+        assert_eq!(parse_whisper_options(), WhisperOptions::default());
+
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_model_mirror() {
+        env::remove_var("HAMMERTALK_MODEL_MIRROR");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_model_mirror(), None);
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "model_mirror = \"https://mirror.example\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            parse_model_mirror(),
+            Some("https://mirror.example".to_string())
+        );
+
+        env::set_var("HAMMERTALK_MODEL_MIRROR", "http://127.0.0.1:8000");
+        assert_eq!(
+            parse_model_mirror(),
+            Some("http://127.0.0.1:8000".to_string())
+        );
+
+        env::remove_var("HAMMERTALK_MODEL_MIRROR");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_preroll_ms() {
+        env::remove_var("HAMMERTALK_PREROLL_MS");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_preroll_ms(false), DEFAULT_PREROLL_MS);
+        assert_eq!(parse_preroll_ms(true), 0);
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("config.toml"), "preroll_ms = 0\n").unwrap();
+        assert_eq!(parse_preroll_ms(false), 0);
+
+        env::set_var("HAMMERTALK_PREROLL_MS", "500");
+        assert_eq!(parse_preroll_ms(false), 500);
+        // A closed microphone has nothing to keep
+        assert_eq!(parse_preroll_ms(true), 0);
+
+        env::remove_var("HAMMERTALK_PREROLL_MS");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_input_device() {
+        env::remove_var("HAMMERTALK_DEVICE");
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp.path());
+        assert_eq!(parse_input_device(), None);
+
+        let config_dir = temp.path().join("hammertalk");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "input_device = \"USB Headset\"\n",
+        )
+        .unwrap();
+        assert_eq!(parse_input_device(), Some("USB Headset".to_string()));
+
+        env::set_var("HAMMERTALK_DEVICE", "2");
+        assert_eq!(parse_input_device(), Some("2".to_string()));
+
+        env::remove_var("HAMMERTALK_DEVICE");
+        env::remove_var("XDG_CONFIG_HOME");
+    }
+
     #[test]
     #[serial]
     fn test_parse_engine_choice_config_file() {
@@ -817,6 +1807,15 @@ mod tests {
         write_state(DaemonState::Transcribing);
         assert_eq!(read_state(), Some(DaemonState::Transcribing));
 
+        write_state(DaemonState::Listening);
+        assert_eq!(read_state(), Some(DaemonState::Listening));
+
+        write_state(DaemonState::Degraded);
+        assert_eq!(read_state(), Some(DaemonState::Degraded));
+
+        write_state(DaemonState::Loading);
+        assert_eq!(read_state(), Some(DaemonState::Loading));
+
         env::remove_var("XDG_RUNTIME_DIR");
     }
 
@@ -849,6 +1848,28 @@ mod tests {
         env::remove_var("XDG_RUNTIME_DIR");
     }
 
+    #[test]
+    #[serial]
+    fn test_get_socket_path_with_xdg_runtime_dir() {
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_RUNTIME_DIR", temp.path());
+
+        let socket_path = get_socket_path();
+
+        assert_eq!(socket_path, temp.path().join("hammertalk.sock"));
+        env::remove_var("XDG_RUNTIME_DIR");
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_socket_path_fallback() {
+        env::remove_var("XDG_RUNTIME_DIR");
+
+        let socket_path = get_socket_path();
+
+        assert_eq!(socket_path, PathBuf::from("/tmp/hammertalk.sock"));
+    }
+
     #[test]
     fn test_format_waybar_json_idle() {
         let json = format_waybar_json(Some(DaemonState::Idle));
@@ -879,6 +1900,33 @@ mod tests {
         assert_eq!(v["text"], "\u{f0a30}");
     }
 
+    #[test]
+    fn test_format_waybar_json_listening() {
+        let json = format_waybar_json(Some(DaemonState::Listening));
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["alt"], "listening");
+        assert_eq!(v["class"], "listening");
+        assert_eq!(v["tooltip"], "Hammertalk: listening");
+    }
+
+    #[test]
+    fn test_format_waybar_json_degraded() {
+        let json = format_waybar_json(Some(DaemonState::Degraded));
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["alt"], "degraded");
+        assert_eq!(v["class"], "degraded");
+        assert_eq!(v["tooltip"], "Hammertalk: no input device");
+    }
+
+    #[test]
+    fn test_format_waybar_json_loading() {
+        let json = format_waybar_json(Some(DaemonState::Loading));
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["alt"], "loading");
+        assert_eq!(v["class"], "loading");
+        assert_eq!(v["tooltip"], "Hammertalk: loading model");
+    }
+
     #[test]
     fn test_format_waybar_json_stopped() {
         let json = format_waybar_json(None);
@@ -889,6 +1937,25 @@ mod tests {
         assert_eq!(v["text"], "\u{f131}");
     }
 
+    #[test]
+    #[serial]
+    fn test_read_pid() {
+        let temp = tempdir().unwrap();
+        env::set_var("XDG_RUNTIME_DIR", temp.path());
+
+        assert_eq!(read_pid(), None);
+
+        write_pid_file().unwrap();
+        assert_eq!(read_pid(), Some(std::process::id()));
+        assert!(is_daemon_running());
+
+        fs::write(get_pid_path(), "garbage\n").unwrap();
+        assert_eq!(read_pid(), None);
+        assert!(!is_daemon_running());
+
+        env::remove_var("XDG_RUNTIME_DIR");
+    }
+
     #[test]
     #[serial]
     fn test_is_daemon_running_no_pid_file() {
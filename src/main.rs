@@ -1,44 +1,65 @@
 use std::io::Write;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::SampleFormat;
-use hammertalk::engine::Engine;
+use cpal::{FromSample, SampleFormat};
+use hammertalk::audio::{
+    default_input_device, downmix, find_input_device, format_preference, input_device_names,
+    roll_preroll, splice_preroll,
+};
+use hammertalk::control::{self, Command, ControlRequest, Reply};
+use hammertalk::dictionary::Dictionary;
+use hammertalk::engine::{self, Transcriber, WhisperOptions};
+use hammertalk::models::{self, Status, Verdict};
+use hammertalk::resample::Resampler;
+use hammertalk::ring::{ring_buffer, Consumer, Producer};
+use hammertalk::vad::{AutoStop, LiveVad, Segmenter};
+use hammertalk::worker::{self, Job, LoadFn, Worker};
 use hammertalk::{
-    fatal_exit, format_waybar_json, get_model_path, is_daemon_running, needs_resample,
-    parse_engine_choice, parse_language, read_state, remove_pid_file, remove_state_file, type_text,
-    write_pid_file, write_state, DaemonState, BUFFER_DRAIN_DELAY_MS, SAMPLE_RATE,
+    default_model_path, fatal_exit, format_waybar_json, get_dictionary_path, get_model_path,
+    get_models_dir, get_pid_path, get_recovery_dir, get_socket_path, is_daemon_running,
+    needs_resample, parse_auto_stop_silence_ms, parse_engine_choice, parse_idle_unload_secs,
+    parse_input_device, parse_language, parse_lazy_mic, parse_max_recording_secs,
+    parse_mic_linger_ms, parse_model_mirror, parse_preroll_ms, parse_segment_pause_ms,
+    parse_spoken_commands, parse_vad, parse_whisper_options, read_pid, read_state,
+    resolve_old_whisper_name, write_pid_file, write_state, DaemonState, EngineChoice, RuntimeFiles,
+    BUFFER_DRAIN_DELAY_MS, DEVICE_CHECK_SECS, DRAIN_INTERVAL_MS, MAX_SEGMENT_SECS,
+    RING_BUFFER_SECS, SAMPLE_RATE, SHUTDOWN_TIMEOUT_SECS,
 };
-use log::{error, info, warn};
-use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1, SIGUSR2};
-use signal_hook::iterator::Signals;
+use log::{debug, error, info, warn};
 
 static RECORDING: AtomicBool = AtomicBool::new(false);
+/// Keep forwarding samples while idle, to fill the pre-roll.
+static PREROLL: AtomicBool = AtomicBool::new(false);
 
-fn record_audio(buffer: Arc<Mutex<Vec<f32>>>) -> Result<cpal::Stream, Box<dyn std::error::Error>> {
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .ok_or("No input device available")?;
-
+/// Open an input stream on `device`. Returns the stream, the ring buffer it fills with
+/// mono samples, and the device sample rate.
+fn record_audio(
+    device: &cpal::Device,
+    health: &StreamHealth,
+) -> Result<(cpal::Stream, Consumer, u32), Box<dyn std::error::Error>> {
     info!("Using input device: {}", device.name()?);
 
     // Try to get a config close to 16kHz mono
     let supported_configs = device.supported_input_configs()?;
 
+    // Prefer a config that covers 16kHz, then the most convenient sample format
     let config = supported_configs
-        .filter(|c| c.sample_format() == SampleFormat::F32)
+        .filter(|c| format_preference(c.sample_format()) != u8::MAX)
         .min_by_key(|c| {
             let min = c.min_sample_rate().0;
             let max = c.max_sample_rate().0;
-            if SAMPLE_RATE >= min && SAMPLE_RATE <= max {
+            let rate_distance = if SAMPLE_RATE >= min && SAMPLE_RATE <= max {
                 0
             } else {
                 (SAMPLE_RATE as i32 - max as i32).abs()
-            }
+            };
+            (rate_distance, format_preference(c.sample_format()))
         })
         .ok_or("No suitable audio config")?;
 
@@ -51,45 +72,602 @@ fn record_audio(buffer: Arc<Mutex<Vec<f32>>>) -> Result<cpal::Stream, Box<dyn st
 
     let config = config.with_sample_rate(cpal::SampleRate(sample_rate));
     let channels = config.channels() as usize;
+    let sample_format = config.sample_format();
 
-    info!("Recording at {} Hz, {} channels", sample_rate, channels);
-
-    let resample_ratio = sample_rate as f32 / SAMPLE_RATE as f32;
-    let should_resample = needs_resample(sample_rate, SAMPLE_RATE);
-
-    let stream = device.build_input_stream(
-        &config.into(),
-        move |data: &[f32], _: &cpal::InputCallbackInfo| {
-            if RECORDING.load(Ordering::SeqCst) {
-                let mut buf = buffer.lock().unwrap();
-
-                // Convert to mono if needed and resample
-                for (i, chunk) in data.chunks(channels).enumerate() {
-                    let sample: f32 = chunk.iter().sum::<f32>() / channels as f32;
-
-                    if should_resample {
-                        // Nearest-neighbor resampling: only push sample when we've moved
-                        // to a new target index, effectively decimating higher sample rates
-                        let target_idx = (i as f32 / resample_ratio) as usize;
-                        let prev_target_idx =
-                            ((i.saturating_sub(1)) as f32 / resample_ratio) as usize;
-                        let is_new_target = target_idx >= buf.len()
-                            || buf.is_empty()
-                            || target_idx != prev_target_idx;
-                        if is_new_target {
-                            buf.push(sample);
-                        }
-                    } else {
-                        buf.push(sample);
-                    }
-                }
+    info!(
+        "Recording at {} Hz, {} channels, {:?} samples",
+        sample_rate, channels, sample_format
+    );
+
+    // The callback runs on the real-time audio thread: it must never lock or allocate,
+    // so it only downmixes into a preallocated ring buffer that the main thread drains.
+    let (producer, consumer) = ring_buffer(sample_rate as usize * RING_BUFFER_SECS);
+
+    let config = config.into();
+    let stream = match sample_format {
+        SampleFormat::I8 => build_input_stream::<i8>(device, &config, channels, producer, health),
+        SampleFormat::I16 => build_input_stream::<i16>(device, &config, channels, producer, health),
+        SampleFormat::I32 => build_input_stream::<i32>(device, &config, channels, producer, health),
+        SampleFormat::I64 => build_input_stream::<i64>(device, &config, channels, producer, health),
+        SampleFormat::U8 => build_input_stream::<u8>(device, &config, channels, producer, health),
+        SampleFormat::U16 => build_input_stream::<u16>(device, &config, channels, producer, health),
+        SampleFormat::U32 => build_input_stream::<u32>(device, &config, channels, producer, health),
+        SampleFormat::U64 => build_input_stream::<u64>(device, &config, channels, producer, health),
+        SampleFormat::F32 => build_input_stream::<f32>(device, &config, channels, producer, health),
+        SampleFormat::F64 => build_input_stream::<f64>(device, &config, channels, producer, health),
+        other => return Err(format!("Unsupported sample format {:?}", other).into()),
+    }?;
+
+    Ok((stream, consumer, sample_rate))
+}
+
+/// Build an input stream for sample type `T`, converting each frame to normalized
+/// mono f32 before it goes into the ring buffer.
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channels: usize,
+    mut producer: Producer,
+    health: &StreamHealth,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: FromSample<T>,
+{
+    let callbacks = Arc::clone(&health.callbacks);
+    let failed = Arc::clone(&health.failed);
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            callbacks.fetch_add(1, Ordering::Relaxed);
+            if RECORDING.load(Ordering::SeqCst) || PREROLL.load(Ordering::Relaxed) {
+                // Convert to mono; resampling happens on the main thread
+                producer.push_iter(data.chunks(channels).map(downmix));
             }
         },
-        |err| error!("Audio stream error: {}", err),
+        move |err| {
+            error!("Audio stream error: {}", err);
+            failed.store(true, Ordering::SeqCst);
+        },
         None,
-    )?;
+    )
+}
+
+/// Shared with the stream callbacks so the main thread can tell the stream has died.
+#[derive(Default)]
+struct StreamHealth {
+    /// Set by the error callback.
+    failed: Arc<AtomicBool>,
+    /// Bumped by the data callback, to notice a stream that silently stopped.
+    callbacks: Arc<AtomicUsize>,
+}
+
+/// The input stream and the device it is open on. Reopens the stream when it fails,
+/// falling back to the default device, and moves back to the preferred device once it
+/// is available again. A lazy microphone is only open while recording, plus `linger`
+/// after each recording.
+struct Microphone {
+    preferred: Option<String>,
+    lazy: bool,
+    linger: Duration,
+    stream: Option<cpal::Stream>,
+    /// When a lazily opened stream is due to close.
+    close_at: Option<Instant>,
+    /// False while no input device can be opened.
+    available: bool,
+    health: StreamHealth,
+    last_callbacks: usize,
+    /// The preferred device is missing, so the default device is in use.
+    on_fallback: bool,
+    last_check: Instant,
+}
+
+impl Microphone {
+    fn new(preferred: Option<String>, lazy: bool, linger: Duration) -> Self {
+        Microphone {
+            preferred,
+            lazy,
+            linger,
+            stream: None,
+            close_at: None,
+            available: false,
+            health: StreamHealth::default(),
+            last_callbacks: 0,
+            on_fallback: false,
+            last_check: Instant::now(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn is_available(&self) -> bool {
+        self.available
+    }
+
+    /// Check for a usable device without opening it, for a lazy microphone.
+    fn probe(&mut self) {
+        self.available = self
+            .preferred
+            .as_deref()
+            .and_then(find_input_device)
+            .or_else(default_input_device)
+            .is_some();
+    }
+
+    /// Open a stream on the preferred device, or the default device if it is missing,
+    /// and point `capture` at it.
+    fn open(&mut self, capture: &mut Capture) -> Result<(), String> {
+        let result = self.try_open(capture);
+        self.available = result.is_ok();
+        result
+    }
+
+    fn try_open(&mut self, capture: &mut Capture) -> Result<(), String> {
+        self.stream = None;
+        self.close_at = None;
+
+        let preferred = self.preferred.as_deref().and_then(find_input_device);
+        let was_on_fallback = self.on_fallback;
+        self.on_fallback = self.preferred.is_some() && preferred.is_none();
+        if self.on_fallback && !was_on_fallback {
+            warn!(
+                "No input device matches '{}', using default. Available: {}",
+                self.preferred.as_deref().unwrap_or_default(),
+                input_device_names().join(", ")
+            );
+        }
+
+        let device = preferred
+            .or_else(default_input_device)
+            .ok_or("No input device available")?;
+        let (stream, ring, device_rate) =
+            record_audio(&device, &self.health).map_err(|e| e.to_string())?;
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start audio stream: {}", e))?;
+
+        capture.set_source(ring, device_rate);
+        self.health.failed.store(false, Ordering::SeqCst);
+        self.last_callbacks = self.health.callbacks.load(Ordering::Relaxed);
+        self.last_check = Instant::now();
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    /// Make sure the stream is open for a recording, cancelling any pending close.
+    fn acquire(&mut self, capture: &mut Capture) -> Result<(), String> {
+        self.close_at = None;
+        if self.is_open() {
+            Ok(())
+        } else {
+            self.open(capture)
+        }
+    }
+
+    fn close(&mut self) {
+        if self.stream.take().is_some() {
+            debug!("Input stream closed");
+        }
+        self.close_at = None;
+    }
+
+    /// Called when a recording ends. A lazy microphone closes once `linger` has passed.
+    fn release(&mut self) {
+        if !self.lazy || !self.is_open() {
+            return;
+        }
+        if self.linger.is_zero() {
+            self.close();
+        } else {
+            self.close_at = Some(Instant::now() + self.linger);
+        }
+    }
+
+    /// Reopen the stream if it failed or stalled, retry while no device is available,
+    /// and switch back to the preferred device when it returns (only between recordings).
+    fn check(&mut self, capture: &mut Capture, recording: bool) {
+        if !recording && self.close_at.is_some_and(|at| Instant::now() >= at) {
+            self.close();
+        }
+
+        let failed = self.health.failed.swap(false, Ordering::SeqCst);
+        if !failed && self.last_check.elapsed() < Duration::from_secs(DEVICE_CHECK_SECS) {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let reason = if failed {
+            Some("Audio stream failed")
+        } else if self.stream.is_none() {
+            if self.available {
+                // Closed on purpose by a lazy microphone
+                return;
+            }
+            if self.lazy {
+                // Don't open the microphone just to find out whether it is back
+                self.probe();
+                if self.available {
+                    info!("Input device available again");
+                }
+                return;
+            }
+            None
+        } else if self.health.callbacks.load(Ordering::Relaxed) == self.last_callbacks {
+            Some("Audio stream stopped delivering data")
+        } else if self.on_fallback
+            && !recording
+            && self
+                .preferred
+                .as_deref()
+                .and_then(find_input_device)
+                .is_some()
+        {
+            Some("Preferred input device is available again")
+        } else {
+            self.last_callbacks = self.health.callbacks.load(Ordering::Relaxed);
+            return;
+        };
+
+        if let Some(reason) = reason {
+            if self.lazy && !recording {
+                // The next recording opens a fresh stream anyway
+                debug!("{}, closing input stream", reason);
+                self.close();
+                return;
+            }
+            info!("{}, reopening input stream", reason);
+        }
+
+        let was_available = self.available;
+        match self.open(capture) {
+            Ok(()) if !was_available => info!("Input device available again"),
+            Ok(()) => {}
+            Err(e) if was_available => {
+                warn!("No usable input device ({}), will keep retrying", e)
+            }
+            Err(e) => debug!("Input device still unavailable: {}", e),
+        }
+    }
+}
+
+/// The utterance being recorded, assembled on the main thread from the ring buffer
+/// that the audio callback fills and resampled to `SAMPLE_RATE`.
+struct Capture {
+    ring: Option<Consumer>,
+    resampler: Resampler,
+    pending: Vec<f32>,
+    samples: Vec<f32>,
+    /// Rolling audio from just before the recording, kept while idle.
+    preroll: Vec<f32>,
+    preroll_len: usize,
+    /// The stream opened since the last recording began, so everything in the ring
+    /// buffer belongs to the next one.
+    fresh: bool,
+}
+
+impl Capture {
+    fn new(preroll_len: usize) -> Self {
+        Capture {
+            ring: None,
+            resampler: Resampler::new(SAMPLE_RATE, SAMPLE_RATE),
+            pending: Vec::new(),
+            samples: Vec::new(),
+            preroll: Vec::with_capacity(preroll_len),
+            preroll_len,
+            fresh: false,
+        }
+    }
+
+    fn has_preroll(&self) -> bool {
+        self.preroll_len > 0
+    }
+
+    /// Switch to the ring buffer of a newly opened stream, keeping what the previous
+    /// stream delivered so a recording survives the device changing underneath it.
+    fn set_source(&mut self, ring: Consumer, device_rate: u32) {
+        self.drain();
+        self.resampler.flush(&mut self.samples);
+        if needs_resample(device_rate, SAMPLE_RATE) {
+            info!("Resampling {} Hz to {} Hz", device_rate, SAMPLE_RATE);
+        }
+        self.resampler = Resampler::new(device_rate, SAMPLE_RATE);
+        self.pending = Vec::with_capacity(ring.capacity());
+        self.ring = Some(ring);
+        self.preroll.clear();
+        self.fresh = true;
+    }
+
+    /// Move everything the callback has produced so far into the utterance.
+    fn drain(&mut self) {
+        let dropped = self.pull();
+        if dropped > 0 {
+            warn!("Audio ring buffer overflow, dropped {} samples", dropped);
+        }
+    }
+
+    /// Like `drain`, also running the new audio through `vad`.
+    fn record(&mut self, vad: &mut LiveVad) {
+        let start = self.samples.len();
+        self.drain();
+        vad.push(&self.samples[start..]);
+    }
+
+    /// Samples recorded so far.
+    fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Pass everything the callback has produced so far on to `segmenter`.
+    fn feed(&mut self, segmenter: &mut Segmenter, segments: &mut Vec<Vec<f32>>) {
+        self.drain();
+        segmenter.push(&self.samples, segments);
+        self.samples.clear();
+    }
+
+    /// Keep the last `preroll_len` samples the callback produced while idle.
+    fn drain_idle(&mut self) {
+        // Overflow while idle (e.g. during transcription) only loses stale audio
+        self.pull();
+        roll_preroll(&mut self.preroll, &self.samples, self.preroll_len);
+        self.samples.clear();
+    }
+
+    /// Resample what's in the ring buffer into `samples`. Returns how many samples the
+    /// callback had to drop since the last call.
+    fn pull(&mut self) -> usize {
+        let Some(ring) = self.ring.as_mut() else {
+            return 0;
+        };
+        ring.pop_into(&mut self.pending);
+        self.resampler.process(&self.pending, &mut self.samples);
+        self.pending.clear();
+        ring.take_dropped()
+    }
+
+    /// Start a new utterance with the pre-roll in front of it. A stream that was just
+    /// opened is captured from its start instead.
+    fn begin(&mut self) {
+        if !std::mem::take(&mut self.fresh) && !self.has_preroll() {
+            self.clear();
+            return;
+        }
+        // The resampler keeps running, so the pre-roll joins up with the recording
+        self.samples.clear();
+        self.pull();
+        splice_preroll(&self.preroll, self.preroll_len, &mut self.samples);
+        self.preroll.clear();
+    }
+
+    fn clear(&mut self) {
+        if let Some(ring) = self.ring.as_mut() {
+            ring.clear();
+            ring.take_dropped();
+        }
+        self.resampler.reset();
+        self.samples.clear();
+        self.preroll.clear();
+    }
+
+    fn take(&mut self) -> Vec<f32> {
+        self.drain();
+        self.resampler.flush(&mut self.samples);
+        std::mem::take(&mut self.samples)
+    }
+}
+
+/// Begin recording, opening the input stream first if it is closed.
+fn start_recording(
+    capture: &mut Capture,
+    mic: &mut Microphone,
+    vad: &mut LiveVad,
+) -> Result<DaemonState, String> {
+    mic.acquire(capture)?;
+    info!("Starting recording...");
+    capture.begin();
+    vad.reset();
+    RECORDING.store(true, Ordering::SeqCst);
+    write_state(DaemonState::Recording);
+    Ok(DaemonState::Recording)
+}
+
+/// State to report while not recording.
+fn idle_state(mic: &Microphone) -> DaemonState {
+    if mic.is_available() {
+        DaemonState::Idle
+    } else {
+        DaemonState::Degraded
+    }
+}
+
+fn is_recording(state: DaemonState) -> bool {
+    matches!(state, DaemonState::Recording | DaemonState::Listening)
+}
+
+/// State to report while not recording, showing an engine switch or queued
+/// transcriptions first.
+fn resting_state(mic: &Microphone, worker: &Worker, switching: bool) -> DaemonState {
+    if switching || worker.is_loading() {
+        DaemonState::Loading
+    } else if worker.is_busy() {
+        DaemonState::Transcribing
+    } else {
+        idle_state(mic)
+    }
+}
+
+/// Answer a `--wait` request once its transcription is done.
+fn respond_with_result(
+    request: &ControlRequest,
+    state: DaemonState,
+    result: Result<String, String>,
+) {
+    request.respond(match result {
+        Ok(text) => Reply::ok(state).with_text(text),
+        Err(e) => Reply::error(state, &e),
+    });
+}
+
+fn cancel_recording(capture: &mut Capture, mic: &mut Microphone) -> DaemonState {
+    info!("Cancelling recording...");
+    RECORDING.store(false, Ordering::SeqCst);
+    capture.clear();
+    mic.release();
+    let state = idle_state(mic);
+    write_state(state);
+    state
+}
+
+/// Stop recording and queue what was captured for transcription. The returned channel
+/// gets the typed text, or an error message when there was nothing to type.
+fn stop_recording(
+    capture: &mut Capture,
+    mic: &mut Microphone,
+    worker: &Worker,
+) -> mpsc::Receiver<Result<String, String>> {
+    info!("Stopping recording...");
+    RECORDING.store(false, Ordering::SeqCst);
+
+    // Small delay to ensure buffer is complete
+    thread::sleep(Duration::from_millis(BUFFER_DRAIN_DELAY_MS));
+
+    let samples = capture.take();
+    capture.clear();
+    mic.release();
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(samples, tx));
+    rx
+}
+
+/// Start hands-free dictation: record continuously and transcribe each utterance as
+/// soon as the speaker pauses.
+fn start_listening(
+    capture: &mut Capture,
+    mic: &mut Microphone,
+    segmenter: &mut Segmenter,
+) -> Result<DaemonState, String> {
+    mic.acquire(capture)?;
+    info!("Listening for continuous dictation...");
+    capture.begin();
+    segmenter.reset();
+    RECORDING.store(true, Ordering::SeqCst);
+    write_state(DaemonState::Listening);
+    Ok(DaemonState::Listening)
+}
+
+/// Stop dictating. The utterance in progress is queued for transcription unless
+/// `discard` is set. Returns the queued job's reply channel, if there was one.
+fn stop_listening(
+    capture: &mut Capture,
+    mic: &mut Microphone,
+    segmenter: &mut Segmenter,
+    worker: &Worker,
+    discard: bool,
+) -> Option<mpsc::Receiver<Result<String, String>>> {
+    info!("Stopping continuous dictation...");
+    RECORDING.store(false, Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(BUFFER_DRAIN_DELAY_MS));
+
+    let mut segments = Vec::new();
+    segmenter.push(&capture.take(), &mut segments);
+    segments.extend(segmenter.finish());
+    mic.release();
+    if discard {
+        return None;
+    }
+
+    let last = segments.pop()?;
+    for segment in segments {
+        worker.submit(Job::new(segment));
+    }
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(last, tx));
+    Some(rx)
+}
+
+/// Create the engine for `choice` and load its model from `path`.
+fn load_engine(
+    choice: &EngineChoice,
+    path: &Path,
+    whisper: &WhisperOptions,
+) -> Result<Box<dyn Transcriber>, String> {
+    check_model(choice, path)?;
+    let mut engine = engine::for_choice_with(choice, whisper);
+    engine.load(path).map_err(|e| e.to_string())?;
+    Ok(engine)
+}
+
+/// Catch missing or corrupt model files before the engine does, with a message that
+/// says how to get them.
+fn check_model(choice: &EngineChoice, path: &Path) -> Result<(), String> {
+    if let Some(model) = models::find(choice).filter(|_| path == default_model_path(choice)) {
+        models::check_installed(model, &get_models_dir())
+    } else if path.exists() {
+        Ok(())
+    } else {
+        Err(format!("model path {:?} does not exist", path))
+    }
+}
+
+/// Loads the model for `choice` again after the worker unloaded it.
+fn engine_loader(choice: EngineChoice, path: PathBuf, whisper: WhisperOptions) -> LoadFn {
+    Arc::new(move || load_engine(&choice, &path, &whisper))
+}
+
+/// Check that `choice` can do what the settings ask of it, before loading it.
+fn check_engine(
+    choice: &EngineChoice,
+    language: Option<&str>,
+    whisper: &WhisperOptions,
+) -> Result<(), String> {
+    engine::check_language(choice, language)?;
+    if whisper.translate && !engine::for_choice(choice).capabilities().translate {
+        warn!(
+            "The {} engine can't translate, transcribing instead",
+            choice
+        );
+    }
+    Ok(())
+}
+
+/// An engine loading on a background thread, to be swapped in once it's ready.
+struct PendingEngine {
+    choice: EngineChoice,
+    path: PathBuf,
+    loaded: mpsc::Receiver<Result<Box<dyn Transcriber>, String>>,
+    /// A `--wait` request to answer when loading is done.
+    request: Option<ControlRequest>,
+}
+
+impl PendingEngine {
+    fn spawn(choice: EngineChoice, whisper: WhisperOptions) -> Self {
+        let (tx, loaded) = mpsc::channel();
+        let path = default_model_path(&choice);
+        info!(
+            "Loading {} engine from {:?} in the background",
+            choice, path
+        );
+        let (loading, loading_path) = (choice.clone(), path.clone());
+        thread::spawn(move || {
+            let _ = tx.send(load_engine(&loading, &loading_path, &whisper));
+        });
+        PendingEngine {
+            choice,
+            path,
+            loaded,
+            request: None,
+        }
+    }
 
-    Ok(stream)
+    /// The loaded engine or why loading failed, once the loader is done.
+    fn poll(&self) -> Option<Result<Box<dyn Transcriber>, String>> {
+        match self.loaded.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("loader thread exited".to_string())),
+        }
+    }
 }
 
 fn run_status(follow: bool, json_format: bool) {
@@ -143,6 +721,286 @@ fn run_status(follow: bool, json_format: bool) {
     }
 }
 
+/// List input devices with the configurations they support. Returns the process exit code.
+fn run_devices() -> i32 {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+
+    let devices = match host.input_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("Failed to list input devices: {}", e);
+            return 1;
+        }
+    };
+
+    for (index, device) in devices.enumerate() {
+        let name = device.name().unwrap_or_else(|_| "<unknown>".to_string());
+        let marker = if default_name.as_deref() == Some(name.as_str()) {
+            " (default)"
+        } else {
+            ""
+        };
+        println!("{}: {}{}", index, name, marker);
+
+        match device.supported_input_configs() {
+            Ok(configs) => {
+                for config in configs {
+                    let (min, max) = (config.min_sample_rate().0, config.max_sample_rate().0);
+                    let rates = if min == max {
+                        format!("{} Hz", min)
+                    } else {
+                        format!("{}-{} Hz", min, max)
+                    };
+                    println!(
+                        "    {} ch, {}, {:?}",
+                        config.channels(),
+                        rates,
+                        config.sample_format()
+                    );
+                }
+            }
+            Err(e) => println!("    (unable to query configs: {})", e),
+        }
+    }
+    0
+}
+
+const MODELS_USAGE: &str = "\
+Usage: hammertalk models <list|download|verify|remove> [engine|all] [--mirror <url>]
+  list               - show each engine's model, its size and whether it is installed
+  download <engine>  - download a model, or every model with `all`
+  verify [engine]    - check installed models against their checksums
+  remove <engine>    - delete a model's files
+
+Exit codes: 0 success, 1 command failed, 2 usage error";
+
+/// The models named by `name`: one engine, or all of them for `all`.
+fn select_models(name: &str) -> Option<Vec<&'static models::Model>> {
+    if name == "all" {
+        return Some(models::MODELS.iter().collect());
+    }
+    match name.parse::<EngineChoice>() {
+        Ok(choice) => match models::find(&choice) {
+            Some(model) => Some(vec![model]),
+            None => {
+                eprintln!("{} has no model to manage", choice);
+                None
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+/// Manage downloaded models. Returns the process exit code.
+fn run_models(args: &[String]) -> i32 {
+    let mut positional = args.iter().enumerate().filter(|(i, a)| {
+        // Skip flags and the value after --mirror
+        !a.starts_with("--") && (*i == 0 || args[i - 1] != "--mirror")
+    });
+    let action = positional.next().map(|(_, a)| a.as_str());
+    let target = positional.next().map(|(_, a)| a.as_str());
+    let dir = get_models_dir();
+
+    match (action, target) {
+        (Some("list"), None) => {
+            println!("Models in {:?}:", dir);
+            for model in models::MODELS {
+                let status = match models::status(model, &dir) {
+                    Status::Installed => "installed".to_string(),
+                    Status::Partial(missing) => format!("incomplete ({} missing)", missing.len()),
+                    Status::Missing => "not installed".to_string(),
+                };
+                println!(
+                    "  {:<24} {:>6} MB  {}",
+                    model.engine.to_string(),
+                    model.size_mb,
+                    status
+                );
+            }
+            0
+        }
+        (Some("download"), Some(name)) => {
+            let Some(selected) = select_models(name) else {
+                return 2;
+            };
+            let mirror = parse_model_mirror();
+            for model in selected {
+                println!("{} (~{} MB)", model.engine, model.size_mb);
+                for file in model.files {
+                    println!("  {}", file.path);
+                    match models::download_file(file, &dir, mirror.as_deref()) {
+                        Ok(true) => {}
+                        Ok(false) => println!("    already downloaded"),
+                        Err(e) => {
+                            eprintln!("Failed to download {}: {}", file.path, e);
+                            return 1;
+                        }
+                    }
+                }
+            }
+            0
+        }
+        (Some("verify"), name) => {
+            let selected = match name {
+                Some(name) => match select_models(name) {
+                    Some(selected) => selected,
+                    None => return 2,
+                },
+                // Without a name, check whatever is at least partly installed
+                None => models::MODELS
+                    .iter()
+                    .filter(|m| models::status(m, &dir) != Status::Missing)
+                    .collect(),
+            };
+            let mut failed = false;
+            for model in selected {
+                let results = match models::verify(model, &dir) {
+                    Ok(results) => results,
+                    Err(e) => {
+                        eprintln!("Failed to verify {}: {}", model.engine, e);
+                        return 1;
+                    }
+                };
+                for (path, verdict) in results {
+                    let line = match verdict {
+                        Verdict::Ok => "ok".to_string(),
+                        Verdict::Missing => {
+                            failed = true;
+                            "missing".to_string()
+                        }
+                        Verdict::Mismatch(expected) => {
+                            failed = true;
+                            format!("checksum mismatch (expected {})", expected)
+                        }
+                        Verdict::Unrecorded => "no checksum recorded".to_string(),
+                    };
+                    println!("{}: {}", path, line);
+                }
+            }
+            if failed {
+                eprintln!("Fetch bad models again with `hammertalk models download <engine>`");
+                return 1;
+            }
+            0
+        }
+        (Some("remove"), Some(name)) => {
+            let Some(selected) = select_models(name) else {
+                return 2;
+            };
+            for model in selected {
+                if let Err(e) = models::remove(model, &dir) {
+                    eprintln!("Failed to remove {}: {}", model.engine, e);
+                    return 1;
+                }
+                println!("Removed {}", model.engine);
+            }
+            0
+        }
+        _ => {
+            eprintln!("{}", MODELS_USAGE);
+            2
+        }
+    }
+}
+
+const CTL_USAGE: &str = "\
+Usage: hammertalk ctl <start|stop|toggle|listen|engine <name>|cancel|status|kill> [--wait]
+  start   - begin recording
+  stop    - stop recording and transcribe
+  toggle  - start recording, or stop and transcribe if recording
+  listen  - dictate hands-free, typing each utterance at a pause, until stop
+  engine  - load another engine in the background and switch to it when ready
+  cancel  - discard the recording without transcribing
+  status  - show whether the daemon is running and what it is doing
+  kill    - shut down the daemon
+
+Options:
+  --wait  with stop/toggle, block until transcription finishes and print the text;
+          with engine, block until the new engine is loaded
+
+Exit codes: 0 success, 1 command failed, 2 usage error, 3 daemon not running";
+
+/// Client side of the control socket. Returns the process exit code.
+fn run_ctl(args: &[String]) -> i32 {
+    let wait = args.iter().any(|a| a == "--wait");
+    let mut positional = args.iter().filter(|a| !a.starts_with("--"));
+    let Some(action) = positional.next() else {
+        eprintln!("{}", CTL_USAGE);
+        return 2;
+    };
+
+    let command = match action.as_str() {
+        "start" => Command::Start,
+        "stop" => Command::Stop { wait },
+        "toggle" => Command::Toggle { wait },
+        "listen" => Command::Listen,
+        "engine" => match positional.next() {
+            Some(engine) => Command::SetEngine {
+                engine: engine.clone(),
+                wait,
+            },
+            None => {
+                eprintln!("{}", CTL_USAGE);
+                return 2;
+            }
+        },
+        "cancel" => Command::Cancel,
+        "status" => Command::Status,
+        "kill" => Command::Shutdown,
+        _ => {
+            eprintln!("{}", CTL_USAGE);
+            return 2;
+        }
+    };
+
+    if !is_daemon_running() {
+        eprintln!(
+            "hammertalk not running (no live PID in {:?})",
+            get_pid_path()
+        );
+        return 3;
+    }
+
+    let reply = match control::send_command(&command) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!(
+                "Failed to reach hammertalk at {:?}: {}",
+                get_socket_path(),
+                e
+            );
+            return 1;
+        }
+    };
+
+    if !reply.ok {
+        eprintln!(
+            "hammertalk {}: {}",
+            action,
+            reply.error.as_deref().unwrap_or("failed")
+        );
+        return 1;
+    }
+
+    match command {
+        Command::Status => {
+            let pid = read_pid().map(|p| p.to_string()).unwrap_or_default();
+            println!("hammertalk running (PID {}): {}", pid, reply.state);
+        }
+        Command::Shutdown => println!("hammertalk stopped"),
+        _ => {
+            if let Some(text) = reply.text {
+                println!("{}", text);
+            }
+        }
+    }
+    0
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
@@ -156,6 +1014,18 @@ fn main() {
         return;
     }
 
+    if args.get(1).map(|s| s.as_str()) == Some("ctl") {
+        std::process::exit(run_ctl(&args[2..]));
+    }
+
+    if args.get(1).map(|s| s.as_str()) == Some("devices") {
+        std::process::exit(run_devices());
+    }
+
+    if args.get(1).map(|s| s.as_str()) == Some("models") {
+        std::process::exit(run_models(&args[2..]));
+    }
+
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
         .format_timestamp_millis()
         .init();
@@ -166,6 +1036,7 @@ fn main() {
     if let Err(e) = write_pid_file() {
         fatal_exit(&format!("Failed to write PID file: {}", e));
     }
+    let runtime_files = RuntimeFiles;
 
     // Load model
     let engine_choice = parse_engine_choice();
@@ -178,28 +1049,91 @@ fn main() {
         info!("Language: auto-detect");
     }
 
-    let mut engine = Engine::new(&engine_choice);
-    if let Err(e) = engine.load(&engine_choice, &model_path) {
-        fatal_exit(&format!("Failed to load model: {}", e));
+    let use_vad = parse_vad();
+    if !use_vad {
+        info!("Voice activity detection disabled");
+    }
+
+    let whisper = parse_whisper_options();
+    if let Some(ref prompt) = whisper.initial_prompt {
+        info!("Whisper initial prompt: {}", prompt);
     }
+    if let Err(e) = check_engine(&engine_choice, language.as_deref(), &whisper) {
+        fatal_exit(&format!("Unsupported language: {}", e));
+    }
+
+    write_state(DaemonState::Loading);
+    let engine = match load_engine(&engine_choice, &model_path, &whisper) {
+        Ok(engine) => engine,
+        Err(e) => fatal_exit(&format!("Failed to load model: {}", e)),
+    };
     info!("Model loaded successfully");
+    let worker = Worker::spawn(engine, language.clone(), use_vad);
+    worker.set_dictionary(Dictionary::load(&get_dictionary_path()));
+    let spoken_commands = parse_spoken_commands();
+    if spoken_commands {
+        info!("Spoken punctuation and commands enabled");
+    }
+    worker.set_spoken_commands(spoken_commands);
+    worker.set_loader(engine_loader(engine_choice, model_path, whisper.clone()));
+    let idle_unload = Duration::from_secs(parse_idle_unload_secs());
+    if !idle_unload.is_zero() {
+        info!(
+            "Unloading the model after {}s without use",
+            idle_unload.as_secs()
+        );
+    }
+    let mut last_used = Instant::now();
+    // An engine being loaded to replace the current one
+    let mut next_engine: Option<PendingEngine> = None;
 
-    // Set up audio buffer
-    let audio_buffer: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(Vec::new()));
+    let pause_ms = parse_segment_pause_ms();
+    let mut segmenter = Segmenter::new(pause_ms as usize, SAMPLE_RATE as usize * MAX_SEGMENT_SECS);
+    let mut segments = Vec::new();
 
-    // Set up audio stream
-    let stream = match record_audio(Arc::clone(&audio_buffer)) {
-        Ok(s) => s,
-        Err(e) => fatal_exit(&format!("Failed to set up audio: {}", e)),
+    let auto_stop = AutoStop {
+        silence_ms: parse_auto_stop_silence_ms() as usize,
+        max_len: (SAMPLE_RATE as u64 * parse_max_recording_secs()) as usize,
     };
+    if auto_stop.silence_ms > 0 {
+        info!("Stopping after {} ms of silence", auto_stop.silence_ms);
+    }
+    let mut live_vad = LiveVad::new();
 
-    // Start the stream (it will only record when RECORDING is true)
-    if let Err(e) = stream.play() {
-        fatal_exit(&format!("Failed to start audio stream: {}", e));
+    // Set up audio stream (it will only record when RECORDING is true). Without a
+    // device the daemon starts degraded and picks one up when it appears.
+    let lazy_mic = parse_lazy_mic();
+    let preroll_ms = parse_preroll_ms(lazy_mic);
+    let mut capture = Capture::new((SAMPLE_RATE as u64 * preroll_ms / 1000) as usize);
+    if capture.has_preroll() {
+        info!("Keeping {} ms of pre-roll", preroll_ms);
+        PREROLL.store(true, Ordering::SeqCst);
+    }
+    let mut mic = Microphone::new(
+        parse_input_device(),
+        lazy_mic,
+        Duration::from_millis(parse_mic_linger_ms()),
+    );
+    if mic.lazy {
+        info!("Microphone opens only while recording");
+        mic.probe();
+        if !mic.is_available() {
+            warn!("No input device available");
+        }
+    } else if let Err(e) = mic.open(&mut capture) {
+        warn!("Failed to set up audio: {}", e);
     }
 
-    // Set up signal handlers
-    let mut signals = Signals::new([SIGUSR1, SIGUSR2, SIGTERM, SIGINT]).unwrap();
+    // Every control path (socket, legacy signals, built-in hotkey) feeds this channel
+    let (commands_tx, commands_rx) = mpsc::channel::<ControlRequest>();
+
+    if let Err(e) = control::spawn_listener(&get_socket_path(), commands_tx.clone()) {
+        fatal_exit(&format!("Failed to set up control socket: {}", e));
+    }
+
+    if let Err(e) = control::spawn_signal_listener(commands_tx.clone()) {
+        fatal_exit(&format!("Failed to set up signal handlers: {}", e));
+    }
 
     // Optionally start built-in hotkey listener (--hotkey "Cmd+Shift+T")
     #[cfg(feature = "hotkey")]
@@ -209,75 +1143,262 @@ fn main() {
             if !hotkey::check_permissions() {
                 fatal_exit("Accessibility permission required for --hotkey");
             }
+            let mode = hotkey::parse_hotkey_mode();
+            let cancel_hotkey = hotkey::parse_cancel_hotkey_arg();
             let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
             let running_clone = Arc::clone(&running);
+            let hotkey_tx = commands_tx.clone();
             thread::spawn(move || {
-                hotkey::run_hotkey_listener(&hotkey_str, running_clone);
+                hotkey::run_hotkey_listener(
+                    &hotkey_str,
+                    mode,
+                    cancel_hotkey.as_deref(),
+                    hotkey_tx,
+                    running_clone,
+                );
             });
             info!("Built-in hotkey listener active");
         }
     }
 
-    info!("Ready. Waiting for signals (USR1=start, USR2=stop)");
-    write_state(DaemonState::Idle);
+    info!("Ready. Waiting for commands (USR1=start, USR2=stop, or the control socket)");
+    let mut state = idle_state(&mic);
+    write_state(state);
+    // `--wait` requests whose transcription is still queued
+    let mut waiting: Vec<(ControlRequest, mpsc::Receiver<Result<String, String>>)> = Vec::new();
 
-    for sig in signals.forever() {
-        match sig {
-            SIGUSR1 => {
-                if !RECORDING.load(Ordering::SeqCst) {
-                    info!("Starting recording...");
-                    audio_buffer.lock().unwrap().clear();
-                    RECORDING.store(true, Ordering::SeqCst);
-                    write_state(DaemonState::Recording);
-                }
+    loop {
+        let received = commands_rx.recv_timeout(Duration::from_millis(DRAIN_INTERVAL_MS));
+
+        mic.check(&mut capture, is_recording(state));
+        if state == DaemonState::Recording {
+            capture.record(&mut live_vad);
+            if let Some(reason) = auto_stop.reason(&live_vad, capture.len()) {
+                info!("{}, stopping automatically", reason);
+                // Same path as a stop command, the text is only typed
+                stop_recording(&mut capture, &mut mic, &worker);
+                state = resting_state(&mic, &worker, next_engine.is_some());
+                write_state(state);
             }
-            SIGUSR2 => {
-                if RECORDING.load(Ordering::SeqCst) {
-                    info!("Stopping recording...");
-                    RECORDING.store(false, Ordering::SeqCst);
-                    write_state(DaemonState::Transcribing);
-
-                    // Small delay to ensure buffer is complete
-                    thread::sleep(Duration::from_millis(BUFFER_DRAIN_DELAY_MS));
-
-                    let samples = {
-                        let buf = audio_buffer.lock().unwrap();
-                        buf.clone()
-                    };
+        } else if state == DaemonState::Listening {
+            capture.feed(&mut segmenter, &mut segments);
+            for segment in segments.drain(..) {
+                worker.submit(Job::new(segment));
+            }
+        } else if capture.has_preroll() {
+            capture.drain_idle();
+        }
+        let recording = is_recording(state);
+        if !recording && state != resting_state(&mic, &worker, next_engine.is_some()) {
+            state = resting_state(&mic, &worker, next_engine.is_some());
+            write_state(state);
+        }
+        waiting.retain(|(request, done)| match done.try_recv() {
+            Ok(result) => {
+                respond_with_result(request, state, result);
+                false
+            }
+            Err(TryRecvError::Empty) => true,
+            Err(TryRecvError::Disconnected) => {
+                request.respond(Reply::error(state, "transcription worker stopped"));
+                false
+            }
+        });
+        // Recordings the model couldn't be loaded for are kept like at shutdown
+        let failed = worker.take_failed();
+        if !failed.is_empty() {
+            worker::save_recordings(&get_recovery_dir(), &failed);
+        }
 
-                    if samples.is_empty() {
-                        warn!("No audio recorded");
-                        write_state(DaemonState::Idle);
-                        continue;
+        if is_recording(state) || worker.is_busy() {
+            last_used = Instant::now();
+        } else if !idle_unload.is_zero()
+            && last_used.elapsed() >= idle_unload
+            && worker.is_loaded()
+            && next_engine.is_none()
+        {
+            info!("Unused for {}s, unloading the model", idle_unload.as_secs());
+            worker.unload();
+            last_used = Instant::now();
+        }
+
+        if let Some(pending) = next_engine.take() {
+            match pending.poll() {
+                None => next_engine = Some(pending),
+                Some(result) => {
+                    let reply = match result {
+                        Ok(engine) => {
+                            info!("Switched to the {} engine", pending.choice);
+                            worker.set_engine(engine);
+                            worker.set_loader(engine_loader(
+                                pending.choice.clone(),
+                                pending.path.clone(),
+                                whisper.clone(),
+                            ));
+                            None
+                        }
+                        Err(e) => {
+                            error!(
+                                "Failed to load the {} engine, keeping the current one: {}",
+                                pending.choice, e
+                            );
+                            Some(e)
+                        }
+                    };
+                    if !is_recording(state) {
+                        state = resting_state(&mic, &worker, false);
+                        write_state(state);
+                    }
+                    if let Some(request) = pending.request {
+                        request.respond(match reply {
+                            None => Reply::ok(state),
+                            Some(e) => Reply::error(state, &e),
+                        });
                     }
+                }
+            }
+        }
 
-                    info!(
-                        "Transcribing {} samples ({:.2}s)...",
-                        samples.len(),
-                        samples.len() as f32 / SAMPLE_RATE as f32
-                    );
+        let request = match received {
+            Ok(request) => request,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
 
-                    match engine.transcribe(samples, language.as_deref()) {
-                        Ok(result) => {
-                            let text = result.text.trim();
-                            info!("Transcription: {}", text);
-                            type_text(text);
-                        }
-                        Err(e) => error!("Transcription failed: {}", e),
+        match request.command {
+            Command::Stop { wait } | Command::Toggle { wait }
+                if state == DaemonState::Listening =>
+            {
+                let done = stop_listening(&mut capture, &mut mic, &mut segmenter, &worker, false);
+                capture.clear();
+                state = resting_state(&mic, &worker, next_engine.is_some());
+                write_state(state);
+                match done {
+                    Some(done) if wait => waiting.push((request, done)),
+                    _ => request.respond(Reply::ok(state)),
+                }
+            }
+            Command::Listen if !recording => {
+                match start_listening(&mut capture, &mut mic, &mut segmenter) {
+                    Ok(new_state) => {
+                        // An unloaded model loads while the user talks
+                        worker.preload();
+                        state = new_state;
+                        request.respond(Reply::ok(state));
+                    }
+                    Err(e) => {
+                        warn!("Cannot start listening: {}", e);
+                        state = idle_state(&mic);
+                        write_state(state);
+                        request.respond(Reply::error(state, "no input device available"));
                     }
-                    write_state(DaemonState::Idle);
                 }
             }
-            SIGTERM | SIGINT => {
+            Command::Listen => request.respond(Reply::error(state, "already recording")),
+            Command::Start | Command::Toggle { .. } if !recording => {
+                match start_recording(&mut capture, &mut mic, &mut live_vad) {
+                    Ok(new_state) => {
+                        worker.preload();
+                        state = new_state;
+                        request.respond(Reply::ok(state));
+                    }
+                    Err(e) => {
+                        warn!("Cannot start recording: {}", e);
+                        state = idle_state(&mic);
+                        write_state(state);
+                        request.respond(Reply::error(state, "no input device available"));
+                    }
+                }
+            }
+            Command::Stop { wait } | Command::Toggle { wait }
+                if state == DaemonState::Recording =>
+            {
+                let done = stop_recording(&mut capture, &mut mic, &worker);
+                state = resting_state(&mic, &worker, next_engine.is_some());
+                write_state(state);
+                if wait {
+                    waiting.push((request, done));
+                } else {
+                    request.respond(Reply::ok(state));
+                }
+            }
+            Command::Start => request.respond(Reply::error(state, "already recording")),
+            Command::Stop { .. } | Command::Toggle { .. } => {
+                request.respond(Reply::error(state, "not recording"))
+            }
+            Command::Cancel => {
+                if state == DaemonState::Listening {
+                    stop_listening(&mut capture, &mut mic, &mut segmenter, &worker, true);
+                    state = cancel_recording(&mut capture, &mut mic);
+                    request.respond(Reply::ok(state));
+                } else if state == DaemonState::Recording {
+                    state = cancel_recording(&mut capture, &mut mic);
+                    request.respond(Reply::ok(state));
+                } else {
+                    request.respond(Reply::error(state, "not recording"));
+                }
+            }
+            Command::SetEngine { ref engine, wait } => {
+                let choice = match engine.parse::<EngineChoice>() {
+                    Ok(choice) => resolve_old_whisper_name(choice),
+                    Err(e) => {
+                        request.respond(Reply::error(state, &e));
+                        continue;
+                    }
+                };
+                if next_engine.is_some() {
+                    request.respond(Reply::error(state, "already loading an engine"));
+                    continue;
+                }
+                if let Err(e) = check_engine(&choice, language.as_deref(), &whisper) {
+                    request.respond(Reply::error(state, &e));
+                    continue;
+                }
+                let mut pending = PendingEngine::spawn(choice, whisper.clone());
+                if !is_recording(state) {
+                    state = DaemonState::Loading;
+                    write_state(state);
+                }
+                if wait {
+                    pending.request = Some(request);
+                } else {
+                    request.respond(Reply::ok(state));
+                }
+                next_engine = Some(pending);
+            }
+            Command::Status => request.respond(Reply::ok(state)),
+            Command::Shutdown => {
                 info!("Shutting down...");
+                // The utterance in progress is typed like after a stop, if there's time
+                if state == DaemonState::Recording {
+                    stop_recording(&mut capture, &mut mic, &worker);
+                } else if state == DaemonState::Listening {
+                    stop_listening(&mut capture, &mut mic, &mut segmenter, &worker, false);
+                }
+                request.respond(Reply::ok(state));
                 break;
             }
-            _ => {}
         }
     }
 
-    drop(stream);
-    remove_state_file();
-    remove_pid_file();
+    drop(mic);
+    worker::shut_down(
+        worker,
+        Duration::from_secs(SHUTDOWN_TIMEOUT_SECS),
+        &get_recovery_dir(),
+    );
+    for (request, done) in waiting {
+        let result = done
+            .try_recv()
+            .unwrap_or_else(|_| Err("daemon shut down before transcribing".to_string()));
+        respond_with_result(&request, DaemonState::Idle, result);
+    }
+    if let Some(request) = next_engine.and_then(|pending| pending.request) {
+        request.respond(Reply::error(
+            DaemonState::Idle,
+            "daemon shut down before the engine loaded",
+        ));
+    }
+    drop(runtime_files);
     info!("Goodbye!");
 }
@@ -0,0 +1,699 @@
+//! Model registry: where each engine's files come from, plus downloading, verifying
+//! and removing them. Backs `hammertalk models`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::EngineChoice;
+
+/// Checksums recorded at download time, in `sha256sum` format, in the models directory.
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+const HUGGING_FACE: &str = "https://huggingface.co";
+
+pub struct ModelFile {
+    /// Path relative to the models directory.
+    pub path: &'static str,
+    pub url: &'static str,
+    /// Expected SHA-256, when pinned. Otherwise the hash recorded at download time is
+    /// what `verify` checks against.
+    pub sha256: Option<&'static str>,
+}
+
+pub struct Model {
+    pub engine: EngineChoice,
+    /// The file or directory the engine loads, relative to the models directory.
+    pub path: &'static str,
+    /// Approximate download size.
+    pub size_mb: u32,
+    pub files: &'static [ModelFile],
+}
+
+const fn file(path: &'static str, url: &'static str) -> ModelFile {
+    ModelFile {
+        path,
+        url,
+        sha256: None,
+    }
+}
+
+pub static MODELS: &[Model] = &[
+    Model {
+        engine: EngineChoice::MoonshineTiny,
+        path: "moonshine-tiny",
+        size_mb: 106,
+        files: &[
+            file(
+                "moonshine-tiny/encoder_model.onnx",
+                "https://huggingface.co/UsefulSensors/moonshine/resolve/main/onnx/merged/tiny/float/encoder_model.onnx",
+            ),
+            file(
+                "moonshine-tiny/decoder_model_merged.onnx",
+                "https://huggingface.co/UsefulSensors/moonshine/resolve/main/onnx/merged/tiny/float/decoder_model_merged.onnx",
+            ),
+            file(
+                "moonshine-tiny/tokenizer.json",
+                "https://huggingface.co/UsefulSensors/moonshine-tiny/resolve/main/tokenizer.json",
+            ),
+        ],
+    },
+    Model {
+        engine: EngineChoice::MoonshineBase,
+        path: "moonshine-base",
+        size_mb: 200,
+        files: &[
+            file(
+                "moonshine-base/encoder_model.onnx",
+                "https://huggingface.co/UsefulSensors/moonshine/resolve/main/onnx/merged/base/float/encoder_model.onnx",
+            ),
+            file(
+                "moonshine-base/decoder_model_merged.onnx",
+                "https://huggingface.co/UsefulSensors/moonshine/resolve/main/onnx/merged/base/float/decoder_model_merged.onnx",
+            ),
+            file(
+                "moonshine-base/tokenizer.json",
+                "https://huggingface.co/UsefulSensors/moonshine-base/resolve/main/tokenizer.json",
+            ),
+        ],
+    },
+    Model {
+        engine: EngineChoice::WhisperTiny,
+        path: "ggml-tiny.bin",
+        size_mb: 75,
+        files: &[file(
+            "ggml-tiny.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperTinyEn,
+        path: "ggml-tiny.en.bin",
+        size_mb: 75,
+        files: &[file(
+            "ggml-tiny.en.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.en.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperBase,
+        path: "ggml-base.bin",
+        size_mb: 148,
+        files: &[file(
+            "ggml-base.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperBaseEn,
+        path: "ggml-base.en.bin",
+        size_mb: 148,
+        files: &[file(
+            "ggml-base.en.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.en.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperSmall,
+        path: "ggml-small.bin",
+        size_mb: 488,
+        files: &[file(
+            "ggml-small.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperSmallEn,
+        path: "ggml-small.en.bin",
+        size_mb: 488,
+        files: &[file(
+            "ggml-small.en.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.en.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperMedium,
+        path: "ggml-medium.bin",
+        size_mb: 1500,
+        files: &[file(
+            "ggml-medium.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperMediumEn,
+        path: "ggml-medium.en.bin",
+        size_mb: 1500,
+        files: &[file(
+            "ggml-medium.en.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.en.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperLargeV3,
+        path: "ggml-large-v3.bin",
+        size_mb: 3100,
+        files: &[file(
+            "ggml-large-v3.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::WhisperLargeV3Turbo,
+        path: "ggml-large-v3-turbo.bin",
+        size_mb: 1600,
+        files: &[file(
+            "ggml-large-v3-turbo.bin",
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo.bin",
+        )],
+    },
+    Model {
+        engine: EngineChoice::ParakeetTdtV3,
+        path: "parakeet-tdt-v3",
+        size_mb: 2400,
+        files: &[
+            file(
+                "parakeet-tdt-v3/encoder-model.onnx",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/encoder-model.onnx",
+            ),
+            file(
+                "parakeet-tdt-v3/encoder-model.onnx.data",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/encoder-model.onnx.data",
+            ),
+            file(
+                "parakeet-tdt-v3/decoder_joint-model.onnx",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/decoder_joint-model.onnx",
+            ),
+            file(
+                "parakeet-tdt-v3/nemo128.onnx",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/nemo128.onnx",
+            ),
+            file(
+                "parakeet-tdt-v3/vocab.txt",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/vocab.txt",
+            ),
+            file(
+                "parakeet-tdt-v3/config.json",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/config.json",
+            ),
+        ],
+    },
+    Model {
+        engine: EngineChoice::ParakeetTdtV3Int8,
+        path: "parakeet-tdt-v3-int8",
+        size_mb: 640,
+        files: &[
+            file(
+                "parakeet-tdt-v3-int8/encoder-model.int8.onnx",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/encoder-model.int8.onnx",
+            ),
+            file(
+                "parakeet-tdt-v3-int8/decoder_joint-model.int8.onnx",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/decoder_joint-model.int8.onnx",
+            ),
+            file(
+                "parakeet-tdt-v3-int8/nemo128.onnx",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/nemo128.onnx",
+            ),
+            file(
+                "parakeet-tdt-v3-int8/vocab.txt",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/vocab.txt",
+            ),
+            file(
+                "parakeet-tdt-v3-int8/config.json",
+                "https://huggingface.co/istupakov/parakeet-tdt-0.6b-v3-onnx/resolve/main/config.json",
+            ),
+        ],
+    },
+];
+
+/// The registry entry for a built-in `engine`.
+pub fn model_for(engine: &EngineChoice) -> &'static Model {
+    find(engine).expect("every built-in engine has a registry entry")
+}
+
+/// The registry entry for `engine`, which custom engines don't have.
+pub fn find(engine: &EngineChoice) -> Option<&'static Model> {
+    MODELS.iter().find(|m| m.engine == *engine)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Installed,
+    /// Some files are there, these aren't.
+    Partial(Vec<&'static str>),
+    Missing,
+}
+
+/// Which of `model`'s files are in `dir`. Only checks that they exist and aren't empty.
+pub fn status(model: &Model, dir: &Path) -> Status {
+    let missing: Vec<&'static str> = model
+        .files
+        .iter()
+        .filter(|f| !is_present(&dir.join(f.path)))
+        .map(|f| f.path)
+        .collect();
+    if missing.is_empty() {
+        Status::Installed
+    } else if missing.len() == model.files.len() {
+        Status::Missing
+    } else {
+        Status::Partial(missing)
+    }
+}
+
+fn is_present(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() > 0)
+}
+
+/// Check before loading: an error that says how to fix a missing, incomplete or
+/// corrupt model, instead of whatever the engine makes of it. Files are hashed against
+/// their pinned or recorded checksum once per process, and again if they change.
+pub fn check_installed(model: &Model, dir: &Path) -> Result<(), String> {
+    match status(model, dir) {
+        Status::Installed => {
+            let recorded = read_checksums(dir).map_err(|e| format!("{}: {}", CHECKSUMS_FILE, e))?;
+            for file in model.files {
+                let path = dir.join(file.path);
+                let Some(expected) = expected_hash(file, &recorded) else {
+                    continue;
+                };
+                if !matches_checksum(&path, &expected).map_err(|e| format!("{:?}: {}", path, e))? {
+                    return Err(format!(
+                        "{} in {:?} does not match its checksum. Run `hammertalk models download {}` to fetch it again",
+                        file.path, dir, model.engine
+                    ));
+                }
+            }
+            Ok(())
+        }
+        Status::Partial(missing) => Err(format!(
+            "the {} model in {:?} is incomplete (missing {}). Run `hammertalk models download {}`",
+            model.engine,
+            dir,
+            missing.join(", "),
+            model.engine
+        )),
+        Status::Missing => Err(format!(
+            "the {} model is not installed in {:?}. Run `hammertalk models download {}`",
+            model.engine, dir, model.engine
+        )),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Ok,
+    Missing,
+    /// The file doesn't match the expected hash, which is given.
+    Mismatch(String),
+    /// Neither pinned nor recorded at download time, so there's nothing to compare to.
+    Unrecorded,
+}
+
+/// Hash each of `model`'s files and compare it to the pinned or recorded checksum.
+pub fn verify(model: &Model, dir: &Path) -> io::Result<Vec<(&'static str, Verdict)>> {
+    let recorded = read_checksums(dir)?;
+    let mut results = Vec::new();
+    for file in model.files {
+        let path = dir.join(file.path);
+        let verdict = if !is_present(&path) {
+            Verdict::Missing
+        } else {
+            match expected_hash(file, &recorded) {
+                None => Verdict::Unrecorded,
+                Some(expected) if sha256_file(&path)? == expected => Verdict::Ok,
+                Some(expected) => Verdict::Mismatch(expected),
+            }
+        };
+        results.push((file.path, verdict));
+    }
+    Ok(results)
+}
+
+/// The pinned checksum of `file`, or else the one recorded when it was downloaded.
+fn expected_hash(file: &ModelFile, recorded: &BTreeMap<String, String>) -> Option<String> {
+    file.sha256
+        .map(str::to_string)
+        .or_else(|| recorded.get(file.path).cloned())
+}
+
+/// Files already hashed by this process, with the size and modification time they had
+/// then, so reloading an unloaded model doesn't hash gigabytes again.
+static CHECKED: Mutex<Option<HashMap<PathBuf, (u64, SystemTime)>>> = Mutex::new(None);
+
+/// Whether `path` hashes to `expected`, skipping the hash if it's unchanged since it
+/// last matched.
+fn matches_checksum(path: &Path, expected: &str) -> io::Result<bool> {
+    let metadata = fs::metadata(path)?;
+    let stamp = (metadata.len(), metadata.modified()?);
+    let mut checked = CHECKED.lock().unwrap();
+    let checked = checked.get_or_insert_with(HashMap::new);
+    if checked.get(path) == Some(&stamp) {
+        return Ok(true);
+    }
+    let matches = sha256_file(path)? == expected;
+    if matches {
+        checked.insert(path.to_path_buf(), stamp);
+    } else {
+        checked.remove(path);
+    }
+    Ok(matches)
+}
+
+/// Where to fetch `file` from, with `mirror` standing in for huggingface.co.
+pub fn file_url(file: &ModelFile, mirror: Option<&str>) -> String {
+    match (mirror, file.url.strip_prefix(HUGGING_FACE)) {
+        (Some(mirror), Some(rest)) => format!("{}{}", mirror.trim_end_matches('/'), rest),
+        _ => file.url.to_string(),
+    }
+}
+
+/// Download `file` into `dir` unless it's already there and matches its checksum, and
+/// record its checksum. Returns whether it was downloaded.
+pub fn download_file(file: &ModelFile, dir: &Path, mirror: Option<&str>) -> Result<bool, String> {
+    let dest = dir.join(file.path);
+    if is_present(&dest) {
+        let recorded = read_checksums(dir).map_err(|e| format!("{}: {}", CHECKSUMS_FILE, e))?;
+        match expected_hash(file, &recorded) {
+            None => return Ok(false),
+            Some(expected) => {
+                let hash = sha256_file(&dest).map_err(|e| format!("{:?}: {}", dest, e))?;
+                if hash == expected {
+                    return Ok(false);
+                }
+                // Corrupt or tampered with: fetch it again over the top
+            }
+        }
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{:?}: {}", parent, e))?;
+    }
+
+    // Download next to the destination so a broken download never looks installed
+    let mut partial = dest.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let url = file_url(file, mirror);
+    let hash = fetch(&url, &partial).inspect_err(|_| {
+        let _ = fs::remove_file(&partial);
+    })?;
+    if let Some(expected) = file.sha256 {
+        if hash != expected {
+            let _ = fs::remove_file(&partial);
+            return Err(format!(
+                "{}: checksum mismatch (expected {}, got {})",
+                url, expected, hash
+            ));
+        }
+    }
+
+    fs::rename(&partial, &dest).map_err(|e| format!("{:?}: {}", dest, e))?;
+    record_checksum(dir, file.path, &hash).map_err(|e| format!("{}: {}", CHECKSUMS_FILE, e))?;
+    Ok(true)
+}
+
+/// Stream `url` into `dest`, returning the SHA-256 of what was written.
+fn fetch(url: &str, dest: &Path) -> Result<String, String> {
+    use ureq::tls::{RootCerts, TlsConfig, TlsProvider};
+
+    let tls = TlsConfig::builder()
+        .provider(TlsProvider::NativeTls)
+        .root_certs(RootCerts::PlatformVerifier)
+        .build();
+    let agent: ureq::Agent = ureq::Agent::config_builder().tls_config(tls).build().into();
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|e| format!("{}: {}", url, e))?;
+
+    let mut reader = response.into_body().into_reader();
+    let mut out = File::create(dest).map_err(|e| format!("{:?}: {}", dest, e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .map_err(|e| format!("{}: {}", url, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        out.write_all(&buf[..n])
+            .map_err(|e| format!("{:?}: {}", dest, e))?;
+    }
+    out.sync_all().map_err(|e| format!("{:?}: {}", dest, e))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Delete `model`'s files from `dir`, and its directory if that ends up empty.
+pub fn remove(model: &Model, dir: &Path) -> io::Result<()> {
+    for file in model.files {
+        match fs::remove_file(dir.join(file.path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    let model_dir = dir.join(model.path);
+    if model_dir.is_dir() {
+        // Only removed when empty, so nothing the user put there is lost
+        let _ = fs::remove_dir(&model_dir);
+    }
+
+    let mut checksums = read_checksums(dir)?;
+    for file in model.files {
+        checksums.remove(file.path);
+    }
+    write_checksums(dir, &checksums)
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Parse `sha256sum` output: `<hash>  <path>` per line.
+fn parse_checksums(contents: &str) -> BTreeMap<String, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (hash, path) = line.split_once(char::is_whitespace)?;
+            let path = path.trim_start().trim_start_matches('*');
+            (!path.is_empty()).then(|| (path.to_string(), hash.to_lowercase()))
+        })
+        .collect()
+}
+
+fn read_checksums(dir: &Path) -> io::Result<BTreeMap<String, String>> {
+    match fs::read_to_string(dir.join(CHECKSUMS_FILE)) {
+        Ok(contents) => Ok(parse_checksums(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_checksums(dir: &Path, checksums: &BTreeMap<String, String>) -> io::Result<()> {
+    let path = dir.join(CHECKSUMS_FILE);
+    if checksums.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let contents: String = checksums
+        .iter()
+        .map(|(path, hash)| format!("{}  {}\n", hash, path))
+        .collect();
+    fs::write(path, contents)
+}
+
+fn record_checksum(dir: &Path, path: &str, hash: &str) -> io::Result<()> {
+    let mut checksums = read_checksums(dir)?;
+    checksums.insert(path.to_string(), hash.to_string());
+    write_checksums(dir, &checksums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_model_path;
+    use tempfile::tempdir;
+
+    const ALL_ENGINES: &[EngineChoice] = &[
+        EngineChoice::MoonshineTiny,
+        EngineChoice::MoonshineBase,
+        EngineChoice::WhisperTiny,
+        EngineChoice::WhisperTinyEn,
+        EngineChoice::WhisperBase,
+        EngineChoice::WhisperBaseEn,
+        EngineChoice::WhisperSmall,
+        EngineChoice::WhisperSmallEn,
+        EngineChoice::WhisperMedium,
+        EngineChoice::WhisperMediumEn,
+        EngineChoice::WhisperLargeV3,
+        EngineChoice::WhisperLargeV3Turbo,
+        EngineChoice::ParakeetTdtV3,
+        EngineChoice::ParakeetTdtV3Int8,
+    ];
+
+    #[test]
+    fn test_registry_covers_every_engine() {
+        for engine in ALL_ENGINES {
+            let model = model_for(engine);
+            assert!(default_model_path(engine).ends_with(model.path));
+            assert!(!model.files.is_empty());
+            for file in model.files {
+                // Files live at the model path, or inside it for directory models
+                assert!(
+                    file.path == model.path || file.path.starts_with(&format!("{}/", model.path)),
+                    "{} is outside {}",
+                    file.path,
+                    model.path
+                );
+                assert!(file.url.starts_with("https://"));
+            }
+        }
+        assert_eq!(MODELS.len(), ALL_ENGINES.len());
+    }
+
+    #[test]
+    fn test_status() {
+        let temp = tempdir().unwrap();
+        let model = model_for(&EngineChoice::MoonshineTiny);
+        assert_eq!(status(model, temp.path()), Status::Missing);
+
+        fs::create_dir_all(temp.path().join("moonshine-tiny")).unwrap();
+        fs::write(temp.path().join("moonshine-tiny/encoder_model.onnx"), "x").unwrap();
+        // Empty files don't count
+        fs::write(temp.path().join("moonshine-tiny/tokenizer.json"), "").unwrap();
+        assert_eq!(
+            status(model, temp.path()),
+            Status::Partial(vec![
+                "moonshine-tiny/decoder_model_merged.onnx",
+                "moonshine-tiny/tokenizer.json"
+            ])
+        );
+
+        fs::write(
+            temp.path().join("moonshine-tiny/decoder_model_merged.onnx"),
+            "x",
+        )
+        .unwrap();
+        fs::write(temp.path().join("moonshine-tiny/tokenizer.json"), "x").unwrap();
+        assert_eq!(status(model, temp.path()), Status::Installed);
+    }
+
+    #[test]
+    fn test_check_installed_says_how_to_fix() {
+        let temp = tempdir().unwrap();
+        let model = model_for(&EngineChoice::WhisperTinyEn);
+        let err = check_installed(model, temp.path()).unwrap_err();
+        assert!(err.contains("not installed"));
+        assert!(err.contains("hammertalk models download whisper-tiny-en"));
+
+        fs::write(temp.path().join("ggml-tiny.en.bin"), "x").unwrap();
+        assert!(check_installed(model, temp.path()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_against_recorded_checksum() {
+        let temp = tempdir().unwrap();
+        let model = model_for(&EngineChoice::WhisperTinyEn);
+        let path = temp.path().join("ggml-tiny.en.bin");
+        fs::write(&path, "model").unwrap();
+        assert_eq!(
+            verify(model, temp.path()).unwrap(),
+            vec![("ggml-tiny.en.bin", Verdict::Unrecorded)]
+        );
+
+        let hash = sha256_file(&path).unwrap();
+        record_checksum(temp.path(), "ggml-tiny.en.bin", &hash).unwrap();
+        assert_eq!(
+            verify(model, temp.path()).unwrap(),
+            vec![("ggml-tiny.en.bin", Verdict::Ok)]
+        );
+
+        fs::write(&path, "truncated").unwrap();
+        assert_eq!(
+            verify(model, temp.path()).unwrap(),
+            vec![("ggml-tiny.en.bin", Verdict::Mismatch(hash))]
+        );
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("abc");
+        fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_parse_checksums() {
+        let checksums = parse_checksums(
+            "ABC123  ggml-tiny.en.bin\ndef456 *moonshine-tiny/tokenizer.json\n\nbogus\n",
+        );
+        assert_eq!(checksums.len(), 2);
+        assert_eq!(checksums["ggml-tiny.en.bin"], "abc123");
+        assert_eq!(checksums["moonshine-tiny/tokenizer.json"], "def456");
+    }
+
+    #[test]
+    fn test_remove_keeps_other_models() {
+        let temp = tempdir().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join("ggml-tiny.en.bin"), "tiny").unwrap();
+        fs::write(dir.join("ggml-base.en.bin"), "base").unwrap();
+        record_checksum(dir, "ggml-tiny.en.bin", "aa").unwrap();
+        record_checksum(dir, "ggml-base.en.bin", "bb").unwrap();
+
+        remove(model_for(&EngineChoice::WhisperTinyEn), dir).unwrap();
+
+        assert!(!dir.join("ggml-tiny.en.bin").exists());
+        assert!(dir.join("ggml-base.en.bin").exists());
+        let checksums = read_checksums(dir).unwrap();
+        assert_eq!(
+            checksums.keys().collect::<Vec<_>>(),
+            vec!["ggml-base.en.bin"]
+        );
+    }
+
+    #[test]
+    fn test_remove_directory_model() {
+        let temp = tempdir().unwrap();
+        let dir = temp.path();
+        let model = model_for(&EngineChoice::ParakeetTdtV3Int8);
+        for file in model.files {
+            fs::create_dir_all(dir.join(file.path).parent().unwrap()).unwrap();
+            fs::write(dir.join(file.path), "x").unwrap();
+        }
+
+        remove(model, dir).unwrap();
+
+        assert!(!dir.join("parakeet-tdt-v3-int8").exists());
+        assert!(!dir.join(CHECKSUMS_FILE).exists());
+    }
+
+    #[test]
+    fn test_file_url_with_mirror() {
+        let model = model_for(&EngineChoice::WhisperBaseEn);
+        assert_eq!(
+            file_url(&model.files[0], None),
+            "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.en.bin"
+        );
+        assert_eq!(
+            file_url(&model.files[0], Some("http://127.0.0.1:8080/")),
+            "http://127.0.0.1:8080/ggerganov/whisper.cpp/resolve/main/ggml-base.en.bin"
+        );
+    }
+}
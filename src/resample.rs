@@ -0,0 +1,347 @@
+//! Band-limited sample rate conversion from the capture device rate to `SAMPLE_RATE`.
+//!
+//! A polyphase windowed-sinc filter: the ratio is reduced to `up / down`, one filter
+//! phase is precomputed for each of the `up` fractional positions, and every output
+//! sample is a dot product of one phase with the surrounding input. The cutoff sits
+//! just below the lower of the two Nyquist frequencies, so content that would alias
+//! is filtered out before decimating. State is carried across calls, so the result
+//! doesn't depend on how the input was split into callback chunks.
+
+use std::f64::consts::PI;
+
+use crate::needs_resample;
+
+/// Zero crossings of the sinc on each side of the centre tap. More means a steeper
+/// transition band at the cost of more work per output sample.
+const ZERO_CROSSINGS: usize = 24;
+
+/// Cutoff as a fraction of the lower Nyquist frequency, leaving room for the
+/// transition band below it.
+const ROLLOFF: f64 = 0.9;
+
+pub struct Resampler {
+    up: usize,
+    down: usize,
+    /// Taps on each side of the output position, in input samples.
+    half: usize,
+    /// `up` phases of `2 * half` taps each.
+    table: Vec<f32>,
+    /// Input not yet fully consumed, starting `half - 1` samples before the next output.
+    history: Vec<f32>,
+    /// Index into `history` of the first tap for the next output.
+    pos: usize,
+    /// Fractional input position of the next output, in units of `1 / up`.
+    phase: usize,
+    total_in: u64,
+    total_out: u64,
+    passthrough: bool,
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-12 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+fn blackman(u: f64) -> f64 {
+    // u in [-1, 1]
+    0.42 + 0.5 * (PI * u).cos() + 0.08 * (2.0 * PI * u).cos()
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let passthrough = !needs_resample(from_rate, to_rate);
+        let divisor = gcd(from_rate as usize, to_rate as usize).max(1);
+        let (up, down) = if passthrough {
+            (1, 1)
+        } else {
+            (to_rate as usize / divisor, from_rate as usize / divisor)
+        };
+
+        // Cutoff relative to the input Nyquist frequency
+        let cutoff = (up as f64 / down as f64).min(1.0) * ROLLOFF;
+        let half = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+        let taps = 2 * half;
+
+        let mut table = Vec::new();
+        if !passthrough {
+            table.reserve(up * taps);
+            for phase in 0..up {
+                let frac = phase as f64 / up as f64;
+                let start = table.len();
+                for j in 0..taps {
+                    let x = (j as f64 - half as f64 + 1.0) - frac;
+                    let h = cutoff * sinc(cutoff * x) * blackman(x / half as f64);
+                    table.push(h as f32);
+                }
+                // Normalise each phase to unity gain at DC
+                let sum: f32 = table[start..].iter().sum();
+                for h in &mut table[start..] {
+                    *h /= sum;
+                }
+            }
+        }
+
+        let mut resampler = Resampler {
+            up,
+            down,
+            half,
+            table,
+            history: Vec::new(),
+            pos: 0,
+            phase: 0,
+            total_in: 0,
+            total_out: 0,
+            passthrough,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    /// Forget all buffered input, ready for an unrelated stream.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        if !self.passthrough {
+            // Zero history so the first output can be centred on the first input sample
+            self.history.resize(self.half - 1, 0.0);
+        }
+        self.pos = 0;
+        self.phase = 0;
+        self.total_in = 0;
+        self.total_out = 0;
+    }
+
+    /// Number of output samples the input seen so far maps to.
+    fn expected_len(&self) -> u64 {
+        (self.total_in * self.up as u64).div_ceil(self.down as u64)
+    }
+
+    /// Resample `input` and append the result to `out`. Output lags the input by about
+    /// `half` input samples until `flush` is called.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        self.total_in += input.len() as u64;
+        if self.passthrough {
+            out.extend_from_slice(input);
+            self.total_out += input.len() as u64;
+            return;
+        }
+        self.history.extend_from_slice(input);
+        self.run(out);
+    }
+
+    /// Emit the remaining output for everything passed to `process` and reset.
+    /// The total output length is exactly `ceil(input_len * to_rate / from_rate)`.
+    pub fn flush(&mut self, out: &mut Vec<f32>) {
+        if !self.passthrough {
+            self.history.resize(self.history.len() + self.half, 0.0);
+            self.run(out);
+        }
+        self.reset();
+    }
+
+    fn run(&mut self, out: &mut Vec<f32>) {
+        let taps = 2 * self.half;
+        let limit = self.expected_len();
+
+        while self.pos + taps <= self.history.len() && self.total_out < limit {
+            let coeffs = &self.table[self.phase * taps..(self.phase + 1) * taps];
+            let window = &self.history[self.pos..self.pos + taps];
+            let sample: f32 = coeffs.iter().zip(window).map(|(h, x)| h * x).sum();
+            out.push(sample);
+            self.total_out += 1;
+
+            self.phase += self.down;
+            self.pos += self.phase / self.up;
+            self.phase %= self.up;
+        }
+
+        // Drop input that no future output will touch
+        let consumed = self.pos.min(self.history.len());
+        self.history.drain(..consumed);
+        self.pos -= consumed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn sine(freq: f64, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * PI * freq * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// Linear sweep from `f0` to `f1` Hz over `len` samples.
+    fn sweep(f0: f64, f1: f64, rate: u32, len: usize) -> Vec<f32> {
+        let duration = len as f64 / rate as f64;
+        (0..len)
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                let phase = 2.0 * PI * (f0 * t + (f1 - f0) * t * t / (2.0 * duration));
+                phase.sin() as f32
+            })
+            .collect()
+    }
+
+    fn resample_all(from: u32, to: u32, input: &[f32]) -> Vec<f32> {
+        let mut resampler = Resampler::new(from, to);
+        let mut out = Vec::new();
+        resampler.process(input, &mut out);
+        resampler.flush(&mut out);
+        out
+    }
+
+    /// Skip the filter's edge transients at both ends.
+    fn middle(samples: &[f32]) -> &[f32] {
+        let edge = samples.len() / 10;
+        &samples[edge..samples.len() - edge]
+    }
+
+    #[test]
+    fn test_exact_output_length() {
+        for &(from, to) in &[
+            (48000, 16000),
+            (44100, 16000),
+            (22050, 16000),
+            (8000, 16000),
+        ] {
+            for &len in &[0usize, 1, 2, 3, 100, 441, 4799, 48000] {
+                let input = vec![0.5; len];
+                let out = resample_all(from, to, &input);
+                let expected = (len as u64 * to as u64).div_ceil(from as u64) as usize;
+                assert_eq!(
+                    out.len(),
+                    expected,
+                    "{} -> {} with {} samples",
+                    from,
+                    to,
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunked_matches_one_shot() {
+        let input = sweep(50.0, 7000.0, 44100, 44100);
+        let expected = resample_all(44100, 16000, &input);
+
+        let mut resampler = Resampler::new(44100, 16000);
+        let mut out = Vec::new();
+        let mut offset = 0;
+        for size in [1, 7, 512, 480, 1024, 33].iter().cycle() {
+            if offset >= input.len() {
+                break;
+            }
+            let end = (offset + size).min(input.len());
+            resampler.process(&input[offset..end], &mut out);
+            offset = end;
+        }
+        resampler.flush(&mut out);
+
+        assert_eq!(out.len(), expected.len());
+        for (a, b) in out.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_passband_tone_preserved() {
+        for &from in &[48000, 44100] {
+            let out = resample_all(from, 16000, &sine(1000.0, from, from as usize));
+            let level = rms(middle(&out));
+            assert!(
+                (level - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01,
+                "1 kHz at {} Hz: rms {}",
+                from,
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn test_passband_sweep_preserved() {
+        let out = resample_all(48000, 16000, &sweep(100.0, 6000.0, 48000, 96000));
+        let level = rms(middle(&out));
+        assert!(
+            (level - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.02,
+            "rms {}",
+            level
+        );
+    }
+
+    #[test]
+    fn test_aliasing_suppressed_48k() {
+        // Everything above 8 kHz would fold back into the speech band
+        let out = resample_all(48000, 16000, &sweep(9000.0, 23000.0, 48000, 96000));
+        let level = rms(middle(&out));
+        assert!(level < 0.01, "alias rms {}", level);
+    }
+
+    #[test]
+    fn test_aliasing_suppressed_44k() {
+        let out = resample_all(44100, 16000, &sweep(9000.0, 21000.0, 44100, 88200));
+        let level = rms(middle(&out));
+        assert!(level < 0.01, "alias rms {}", level);
+    }
+
+    #[test]
+    fn test_nearest_neighbour_would_alias() {
+        // Sanity check for the test above: plain decimation of the same sweep keeps
+        // most of its energy as aliases.
+        let input = sweep(9000.0, 23000.0, 48000, 96000);
+        let decimated: Vec<f32> = input.iter().step_by(3).copied().collect();
+        assert!(rms(middle(&decimated)) > 0.5);
+    }
+
+    #[test]
+    fn test_upsampling_tone_preserved() {
+        let out = resample_all(8000, 16000, &sine(1000.0, 8000, 8000));
+        assert_eq!(out.len(), 16000);
+        let level = rms(middle(&out));
+        assert!(
+            (level - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01,
+            "rms {}",
+            level
+        );
+    }
+
+    #[test]
+    fn test_passthrough() {
+        let input = sine(440.0, 16000, 1000);
+        assert_eq!(resample_all(16000, 16000, &input), input);
+        // Within RESAMPLE_TOLERANCE counts as the same rate
+        assert_eq!(resample_all(16010, 16000, &input), input);
+    }
+
+    #[test]
+    fn test_reset_between_streams() {
+        let input = sine(440.0, 48000, 4800);
+        let mut resampler = Resampler::new(48000, 16000);
+
+        let mut first = Vec::new();
+        resampler.process(&input, &mut first);
+        resampler.flush(&mut first);
+
+        let mut second = Vec::new();
+        resampler.process(&input, &mut second);
+        resampler.flush(&mut second);
+
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,229 @@
+//! Single-producer/single-consumer ring buffer for handing samples from the real-time
+//! audio callback to the main thread. Pushing never locks or allocates; when the buffer
+//! is full, new samples are dropped and counted instead.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Inner {
+    slots: Box<[UnsafeCell<f32>]>,
+    /// Total samples ever written. Only the producer stores to it.
+    head: AtomicUsize,
+    /// Total samples ever read. Only the consumer stores to it.
+    tail: AtomicUsize,
+    /// Samples dropped because the buffer was full.
+    dropped: AtomicUsize,
+}
+
+// Safety: the producer only writes slots in `head..tail + capacity` and the consumer
+// only reads slots in `tail..head`. Those ranges never overlap, and each side publishes
+// its progress with a release store that the other side reads with an acquire load.
+unsafe impl Sync for Inner {}
+unsafe impl Send for Inner {}
+
+impl Inner {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+/// Writing half, owned by the audio callback.
+pub struct Producer {
+    inner: Arc<Inner>,
+}
+
+/// Reading half, owned by the thread that assembles the utterance.
+pub struct Consumer {
+    inner: Arc<Inner>,
+}
+
+/// Allocate a ring buffer holding up to `capacity` samples.
+pub fn ring_buffer(capacity: usize) -> (Producer, Consumer) {
+    assert!(capacity > 0, "ring buffer capacity must be non-zero");
+    let slots = (0..capacity).map(|_| UnsafeCell::new(0.0)).collect();
+    let inner = Arc::new(Inner {
+        slots,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        dropped: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            inner: Arc::clone(&inner),
+        },
+        Consumer { inner },
+    )
+}
+
+impl Producer {
+    /// Push samples until the buffer is full. Returns how many were written;
+    /// the rest are counted as dropped.
+    pub fn push_iter(&mut self, samples: impl Iterator<Item = f32>) -> usize {
+        let inner = &*self.inner;
+        let capacity = inner.capacity();
+        let head = inner.head.load(Ordering::Relaxed);
+        let tail = inner.tail.load(Ordering::Acquire);
+        let free = capacity - head.wrapping_sub(tail);
+
+        let mut written = 0;
+        let mut dropped = 0;
+        for sample in samples {
+            if written < free {
+                let idx = head.wrapping_add(written) % capacity;
+                unsafe { *inner.slots[idx].get() = sample };
+                written += 1;
+            } else {
+                dropped += 1;
+            }
+        }
+
+        inner
+            .head
+            .store(head.wrapping_add(written), Ordering::Release);
+        if dropped > 0 {
+            inner.dropped.fetch_add(dropped, Ordering::Relaxed);
+        }
+        written
+    }
+
+    pub fn push_slice(&mut self, samples: &[f32]) -> usize {
+        self.push_iter(samples.iter().copied())
+    }
+}
+
+impl Consumer {
+    /// Move every available sample into `out`. Returns how many were moved.
+    pub fn pop_into(&mut self, out: &mut Vec<f32>) -> usize {
+        let inner = &*self.inner;
+        let capacity = inner.capacity();
+        let tail = inner.tail.load(Ordering::Relaxed);
+        let head = inner.head.load(Ordering::Acquire);
+        let available = head.wrapping_sub(tail);
+
+        out.reserve(available);
+        for i in 0..available {
+            let idx = tail.wrapping_add(i) % capacity;
+            out.push(unsafe { *inner.slots[idx].get() });
+        }
+
+        inner
+            .tail
+            .store(tail.wrapping_add(available), Ordering::Release);
+        available
+    }
+
+    /// Discard every available sample.
+    pub fn clear(&mut self) {
+        let head = self.inner.head.load(Ordering::Acquire);
+        self.inner.tail.store(head, Ordering::Release);
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.inner.head.load(Ordering::Acquire);
+        let tail = self.inner.tail.load(Ordering::Relaxed);
+        head.wrapping_sub(tail)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Number of samples dropped since the last call because the buffer was full.
+    pub fn take_dropped(&mut self) -> usize {
+        self.inner.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_push_and_pop() {
+        let (mut producer, mut consumer) = ring_buffer(8);
+        assert!(consumer.is_empty());
+
+        assert_eq!(producer.push_slice(&[1.0, 2.0, 3.0]), 3);
+        assert_eq!(consumer.len(), 3);
+
+        let mut out = Vec::new();
+        assert_eq!(consumer.pop_into(&mut out), 3);
+        assert_eq!(out, vec![1.0, 2.0, 3.0]);
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn test_full_buffer_drops_and_counts() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+
+        assert_eq!(producer.push_slice(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]), 4);
+        assert_eq!(consumer.take_dropped(), 2);
+        assert_eq!(consumer.take_dropped(), 0);
+
+        let mut out = Vec::new();
+        consumer.pop_into(&mut out);
+        assert_eq!(out, vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_wraps_around() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        let mut out = Vec::new();
+
+        for round in 0..10 {
+            let base = round as f32 * 3.0;
+            producer.push_slice(&[base, base + 1.0, base + 2.0]);
+            consumer.pop_into(&mut out);
+        }
+
+        let expected: Vec<f32> = (0..30).map(|i| i as f32).collect();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_clear() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        producer.push_slice(&[1.0, 2.0]);
+        consumer.clear();
+        assert!(consumer.is_empty());
+
+        producer.push_slice(&[3.0]);
+        let mut out = Vec::new();
+        consumer.pop_into(&mut out);
+        assert_eq!(out, vec![3.0]);
+    }
+
+    #[test]
+    fn test_concurrent_producer_consumer() {
+        let (mut producer, mut consumer) = ring_buffer(64);
+        let total = 100_000;
+
+        let writer = thread::spawn(move || {
+            let mut next = 0;
+            while next < total {
+                let end = (next + 17).min(total);
+                let written = producer.push_iter((next..end).map(|i| i as f32));
+                next += written;
+                if written == 0 {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut out = Vec::with_capacity(total);
+        while out.len() < total {
+            if consumer.pop_into(&mut out) == 0 {
+                thread::yield_now();
+            }
+        }
+        writer.join().unwrap();
+
+        assert!(out.iter().enumerate().all(|(i, &s)| s == i as f32));
+    }
+}
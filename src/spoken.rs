@@ -0,0 +1,315 @@
+//! Spoken punctuation and editing commands: "comma", "new line", "scratch that" and
+//! the like become symbols and key presses instead of words.
+
+use crate::{Input, Key};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    /// Punctuation that sticks to the word before it.
+    Punctuation(&'static str),
+    /// Ends a sentence, so the next word is capitalized.
+    SentenceEnd(&'static str),
+    OpenQuote,
+    CloseQuote,
+    NewLine,
+    NewParagraph,
+    /// Drop what was said before it in this recording, or the previous recording.
+    ScratchThat,
+}
+
+/// Command phrases, as lowercase words.
+const COMMANDS: &[(&[&str], Command)] = &[
+    (&["period"], Command::SentenceEnd(".")),
+    (&["full", "stop"], Command::SentenceEnd(".")),
+    (&["question", "mark"], Command::SentenceEnd("?")),
+    (&["exclamation", "mark"], Command::SentenceEnd("!")),
+    (&["exclamation", "point"], Command::SentenceEnd("!")),
+    (&["comma"], Command::Punctuation(",")),
+    (&["colon"], Command::Punctuation(":")),
+    (&["semicolon"], Command::Punctuation(";")),
+    (&["open", "quote"], Command::OpenQuote),
+    (&["close", "quote"], Command::CloseQuote),
+    (&["end", "quote"], Command::CloseQuote),
+    (&["new", "line"], Command::NewLine),
+    (&["new", "paragraph"], Command::NewParagraph),
+    (&["scratch", "that"], Command::ScratchThat),
+];
+
+/// Punctuation engines add on their own, which a spoken command replaces.
+const ENGINE_PUNCTUATION: &[char] = &['.', ',', '!', '?', ';', ':'];
+
+/// Turn the commands in `text` into symbols and keys. `previous` is how many characters
+/// the last recording typed, for "scratch that" at the start of this one.
+pub fn interpret(text: &str, previous: usize) -> Vec<Input> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut out = Builder::new(previous);
+    let mut i = 0;
+    while i < words.len() {
+        match match_command(&words[i..]) {
+            Some((len, command)) => {
+                out.command(command);
+                i += len;
+            }
+            None => {
+                out.word(words[i]);
+                i += 1;
+            }
+        }
+    }
+    out.finish()
+}
+
+/// The command `words` start with, and how many words it takes.
+fn match_command(words: &[&str]) -> Option<(usize, Command)> {
+    COMMANDS
+        .iter()
+        .filter(|(phrase, _)| {
+            phrase.len() <= words.len()
+                && phrase
+                    .iter()
+                    .zip(words)
+                    .all(|(expected, word)| normalize(word) == *expected)
+        })
+        .max_by_key(|(phrase, _)| phrase.len())
+        .map(|(phrase, command)| (phrase.len(), *command))
+}
+
+/// `word` lowercased, without the punctuation engines put around it.
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+struct Builder {
+    inputs: Vec<Input>,
+    /// Text typed since the last key press.
+    text: String,
+    /// Characters the previous recording left on screen, until scratched.
+    previous: usize,
+    /// No space before the next word: at the start of a line or after an open quote.
+    glue: bool,
+    capitalize: bool,
+}
+
+impl Builder {
+    fn new(previous: usize) -> Self {
+        Builder {
+            inputs: Vec::new(),
+            text: String::new(),
+            previous,
+            glue: true,
+            capitalize: false,
+        }
+    }
+
+    fn word(&mut self, word: &str) {
+        if !self.glue && !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        if std::mem::take(&mut self.capitalize) {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                self.text.extend(first.to_uppercase());
+                self.text.push_str(chars.as_str());
+            }
+        } else {
+            self.text.push_str(word);
+        }
+        self.glue = false;
+    }
+
+    /// Add `symbol` right after the previous word, in place of any punctuation the
+    /// engine put there.
+    fn attach(&mut self, symbol: &str) {
+        if self.text.is_empty() && self.inputs.is_empty() && self.previous > 0 {
+            // Right after the previous recording: take back the space typed after it
+            self.inputs.push(Input::Key(Key::Backspace));
+            self.previous -= 1;
+        }
+        let trimmed = self.text.trim_end_matches(ENGINE_PUNCTUATION).len();
+        self.text.truncate(trimmed);
+        self.text.push_str(symbol);
+        self.glue = false;
+    }
+
+    fn press(&mut self, key: Key) {
+        if !self.text.is_empty() {
+            self.inputs
+                .push(Input::Text(std::mem::take(&mut self.text)));
+        }
+        self.inputs.push(Input::Key(key));
+        self.glue = true;
+    }
+
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::Punctuation(symbol) => self.attach(symbol),
+            Command::SentenceEnd(symbol) => {
+                self.attach(symbol);
+                self.capitalize = true;
+            }
+            Command::OpenQuote => {
+                // The quoted word gets the capital, not the quote mark
+                let capitalize = std::mem::take(&mut self.capitalize);
+                self.word("\"");
+                self.capitalize = capitalize;
+                self.glue = true;
+            }
+            Command::CloseQuote => self.attach("\""),
+            Command::NewLine => self.press(Key::Enter),
+            Command::NewParagraph => {
+                self.press(Key::Enter);
+                self.press(Key::Enter);
+                self.capitalize = true;
+            }
+            Command::ScratchThat => {
+                if self.text.is_empty() && self.inputs.is_empty() {
+                    let erase = std::mem::take(&mut self.previous);
+                    self.inputs.extend(vec![Input::Key(Key::Backspace); erase]);
+                } else {
+                    // Keep backspaces already erasing the previous recording
+                    self.inputs.retain(|i| *i == Input::Key(Key::Backspace));
+                    self.text.clear();
+                }
+                self.glue = true;
+                self.capitalize = false;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Input> {
+        if !self.text.is_empty() {
+            self.inputs.push(Input::Text(self.text));
+        }
+        self.inputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_input;
+
+    fn spoken(text: &str) -> String {
+        render_input(&interpret(text, 0))
+    }
+
+    #[test]
+    fn test_plain_text_unchanged() {
+        assert_eq!(
+            interpret("Hello there, world.", 0),
+            vec![Input::Text("Hello there, world.".to_string())]
+        );
+        assert!(interpret("", 0).is_empty());
+    }
+
+    #[test]
+    fn test_punctuation() {
+        assert_eq!(
+            spoken("hello comma world period how are you question mark"),
+            "hello, world. How are you?"
+        );
+        assert_eq!(spoken("wait exclamation point"), "wait!");
+        assert_eq!(
+            spoken("note colon buy milk semicolon eggs"),
+            "note: buy milk; eggs"
+        );
+    }
+
+    #[test]
+    fn test_replaces_engine_punctuation() {
+        // What Whisper makes of "hello comma world period"
+        assert_eq!(spoken("Hello, comma, world. Period."), "Hello, world.");
+        assert_eq!(spoken("Is it? Question mark."), "Is it?");
+    }
+
+    #[test]
+    fn test_commands_ignore_case() {
+        assert_eq!(spoken("yes Full Stop"), "yes.");
+        assert_eq!(spoken("one NEW LINE two"), "one\ntwo");
+    }
+
+    #[test]
+    fn test_new_line_presses_enter() {
+        assert_eq!(
+            interpret("Dear Sam, new line thanks", 0),
+            vec![
+                Input::Text("Dear Sam,".to_string()),
+                Input::Key(Key::Enter),
+                Input::Text("thanks".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_paragraph() {
+        assert_eq!(
+            interpret("done new paragraph next", 0),
+            vec![
+                Input::Text("done".to_string()),
+                Input::Key(Key::Enter),
+                Input::Key(Key::Enter),
+                Input::Text("Next".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quotes() {
+        assert_eq!(
+            spoken("she said open quote hi there close quote and left"),
+            "she said \"hi there\" and left"
+        );
+        assert_eq!(spoken("open quote yes end quote"), "\"yes\"");
+        assert_eq!(spoken("done period open quote go"), "done. \"Go");
+    }
+
+    #[test]
+    fn test_scratch_that_drops_earlier_words() {
+        assert_eq!(
+            spoken("send it to Bob scratch that send it to Alice"),
+            "send it to Alice"
+        );
+        assert_eq!(spoken("one new line two scratch that"), "");
+    }
+
+    #[test]
+    fn test_scratch_that_erases_previous_recording() {
+        assert_eq!(
+            interpret("scratch that", 6),
+            vec![Input::Key(Key::Backspace); 6]
+        );
+        let inputs = interpret("Scratch that. Try again", 3);
+        assert_eq!(inputs[..3], vec![Input::Key(Key::Backspace); 3]);
+        assert_eq!(inputs[3], Input::Text("Try again".to_string()));
+
+        // Words after the backspaces can still be scratched without losing them
+        let inputs = interpret("scratch that oops scratch that fine", 2);
+        assert_eq!(
+            inputs,
+            vec![
+                Input::Key(Key::Backspace),
+                Input::Key(Key::Backspace),
+                Input::Text("fine".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_punctuation_continues_previous_recording() {
+        // The previous recording ended in a space, which the comma replaces
+        assert_eq!(
+            interpret("comma and then", 5),
+            vec![
+                Input::Key(Key::Backspace),
+                Input::Text(", and then".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_words_inside_phrases_are_text() {
+        assert_eq!(spoken("the new car"), "the new car");
+        assert_eq!(spoken("scratch the surface"), "scratch the surface");
+    }
+}
@@ -0,0 +1,669 @@
+//! Energy-based voice activity detection.
+//!
+//! Audio at `SAMPLE_RATE` is split into short frames, and each frame's level is compared
+//! with a threshold derived from the clip itself: a noise floor taken from its quietest
+//! frames plus a margin, capped below the loudest frame so a clip that is speech from
+//! end to end still counts as speech. A clip whose loudest frame isn't a margin above
+//! its floor is steady noise, not speech, and nothing below an absolute minimum level
+//! is ever speech, which is what lets an empty clip be skipped entirely.
+//!
+//! `Segmenter` does the same on a live stream for hands-free dictation, with a noise
+//! floor taken from the first few frames that adapts as it goes, and cuts an utterance
+//! off at each pause.
+
+use std::ops::Range;
+
+use crate::SAMPLE_RATE;
+
+/// Length of one analysis frame.
+pub const FRAME_MS: usize = 30;
+
+/// Samples in one analysis frame.
+pub const FRAME_LEN: usize = SAMPLE_RATE as usize * FRAME_MS / 1000;
+
+/// How far above the noise floor (and below the peak) the speech threshold sits.
+const SPEECH_MARGIN_DB: f32 = 10.0;
+
+/// Frames quieter than this are never speech, however quiet the room is.
+pub const MIN_SPEECH_DB: f32 = -55.0;
+
+/// Fraction of frames assumed to be quieter than the noise floor.
+const NOISE_PERCENTILE: f32 = 0.1;
+
+/// Less speech than this in total counts as none at all (clicks, bumps, key presses).
+const MIN_SPEECH_MS: usize = 120;
+
+/// Silence kept on each side of the speech so soft onsets and endings survive.
+const PADDING_MS: usize = 200;
+
+/// How fast the live noise floor creeps up per frame, so it follows a room getting
+/// noisier without mistaking a long stretch of speech for noise.
+const FLOOR_RISE_DB: f32 = 0.02;
+
+/// The live noise floor starts out as the quietest of this many frames, about 300 ms.
+const CALIBRATION_FRAMES: usize = 10;
+
+/// Level of `frame` in dBFS.
+pub fn level_db(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return -100.0;
+    }
+    let mean_square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+    10.0 * mean_square.max(1e-10).log10()
+}
+
+/// Level of each frame of `samples`, including a trailing partial frame.
+pub fn frame_levels(samples: &[f32]) -> Vec<f32> {
+    samples.chunks(FRAME_LEN).map(level_db).collect()
+}
+
+/// Level above which a frame counts as speech, given every frame level of a clip, or
+/// `None` if nothing in the clip stands out from its noise floor.
+pub fn speech_threshold(levels: &[f32]) -> Option<f32> {
+    if levels.is_empty() {
+        return None;
+    }
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let floor = sorted[((sorted.len() - 1) as f32 * NOISE_PERCENTILE) as usize];
+    let peak = sorted[sorted.len() - 1];
+    if peak - floor < SPEECH_MARGIN_DB {
+        return None;
+    }
+    Some(
+        (floor + SPEECH_MARGIN_DB)
+            .min(peak - SPEECH_MARGIN_DB)
+            .max(MIN_SPEECH_DB),
+    )
+}
+
+/// The part of `samples` that holds speech, padded on both sides, or `None` if
+/// there's no speech at all.
+pub fn speech_range(samples: &[f32]) -> Option<Range<usize>> {
+    let levels = frame_levels(samples);
+    let threshold = speech_threshold(&levels)?;
+    let is_speech = |level: &f32| *level > threshold;
+
+    let speech_frames = levels.iter().filter(|l| is_speech(l)).count();
+    if speech_frames * FRAME_MS < MIN_SPEECH_MS {
+        return None;
+    }
+
+    let first = levels.iter().position(is_speech)?;
+    let last = levels.iter().rposition(is_speech)?;
+    let padding = SAMPLE_RATE as usize * PADDING_MS / 1000;
+    let start = (first * FRAME_LEN).saturating_sub(padding);
+    let end = ((last + 1) * FRAME_LEN + padding).min(samples.len());
+    Some(start..end)
+}
+
+/// Call `f` with each complete frame of `partial` followed by `samples`, leaving any
+/// leftover samples in `partial`.
+fn for_each_frame(partial: &mut Vec<f32>, samples: &[f32], mut f: impl FnMut(&[f32])) {
+    let mut samples = samples;
+    while !samples.is_empty() {
+        let take = (FRAME_LEN - partial.len()).min(samples.len());
+        partial.extend_from_slice(&samples[..take]);
+        samples = &samples[take..];
+
+        if partial.len() == FRAME_LEN {
+            f(partial);
+            partial.clear();
+        }
+    }
+}
+
+/// Speech detection on a live stream, with a noise floor that adapts as it goes.
+pub struct LiveVad {
+    partial: Vec<f32>,
+    noise_floor: f32,
+    /// Frames left before the noise floor is taken from the stream so far.
+    calibrating: usize,
+    speech_frames: usize,
+    silent_frames: usize,
+}
+
+impl Default for LiveVad {
+    fn default() -> Self {
+        LiveVad {
+            partial: Vec::with_capacity(FRAME_LEN),
+            noise_floor: f32::INFINITY,
+            calibrating: CALIBRATION_FRAMES,
+            speech_frames: 0,
+            silent_frames: 0,
+        }
+    }
+}
+
+impl LiveVad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Classify one frame as speech or not, updating the noise floor and counters.
+    pub fn classify(&mut self, frame: &[f32]) -> bool {
+        let level = level_db(frame);
+        if self.calibrating > 0 {
+            // Start from the room's own level rather than creeping up to it
+            self.calibrating -= 1;
+            self.noise_floor = self.noise_floor.min(level);
+        }
+        let threshold = (self.noise_floor + SPEECH_MARGIN_DB).max(MIN_SPEECH_DB);
+        let is_speech = level > threshold;
+        self.noise_floor = if level < self.noise_floor {
+            level
+        } else {
+            self.noise_floor + FLOOR_RISE_DB
+        };
+
+        if is_speech {
+            self.speech_frames += 1;
+            self.silent_frames = 0;
+        } else {
+            self.silent_frames += 1;
+        }
+        is_speech
+    }
+
+    /// Classify samples of any length, a frame at a time.
+    pub fn push(&mut self, samples: &[f32]) {
+        let mut partial = std::mem::take(&mut self.partial);
+        for_each_frame(&mut partial, samples, |frame| {
+            self.classify(frame);
+        });
+        self.partial = partial;
+    }
+
+    /// Whether there has been enough speech since the last reset to count as any.
+    pub fn heard_speech(&self) -> bool {
+        self.speech_frames * FRAME_MS >= MIN_SPEECH_MS
+    }
+
+    /// Milliseconds since the last frame of speech, or since the reset if there was none.
+    pub fn silence_ms(&self) -> usize {
+        self.silent_frames * FRAME_MS
+    }
+
+    /// Start counting afresh, learning the noise floor again from the first frames.
+    pub fn reset(&mut self) {
+        self.partial.clear();
+        self.noise_floor = f32::INFINITY;
+        self.calibrating = CALIBRATION_FRAMES;
+        self.speech_frames = 0;
+        self.silent_frames = 0;
+    }
+}
+
+/// Limits that end a push-to-talk recording without a stop command. Zero disables one.
+pub struct AutoStop {
+    /// Silence after speech, in milliseconds.
+    pub silence_ms: usize,
+    /// Recording length, in samples.
+    pub max_len: usize,
+}
+
+impl AutoStop {
+    /// Why the recording should stop now, if it should.
+    pub fn reason(&self, vad: &LiveVad, recorded: usize) -> Option<String> {
+        if self.max_len > 0 && recorded >= self.max_len {
+            return Some(format!(
+                "Recording reached {}s",
+                self.max_len / SAMPLE_RATE as usize
+            ));
+        }
+        if self.silence_ms > 0 && vad.heard_speech() && vad.silence_ms() >= self.silence_ms {
+            return Some(format!("{} ms of silence", vad.silence_ms()));
+        }
+        None
+    }
+}
+
+/// Splits a live stream of samples into utterances, cutting at each pause of at least
+/// `pause_ms` and forcing a cut once an utterance reaches `max_len` samples.
+pub struct Segmenter {
+    vad: LiveVad,
+    pause_frames: usize,
+    max_len: usize,
+    padding: usize,
+    /// Samples that don't fill a whole frame yet.
+    partial: Vec<f32>,
+    /// Recent audio before the current utterance, at most `padding` samples.
+    lead: Vec<f32>,
+    segment: Vec<f32>,
+    in_speech: bool,
+    /// Frames of speech in the current segment.
+    speech_frames: usize,
+}
+
+impl Segmenter {
+    pub fn new(pause_ms: usize, max_len: usize) -> Self {
+        Segmenter {
+            vad: LiveVad::new(),
+            pause_frames: pause_ms.div_ceil(FRAME_MS).max(1),
+            max_len,
+            padding: SAMPLE_RATE as usize * PADDING_MS / 1000,
+            partial: Vec::with_capacity(FRAME_LEN),
+            lead: Vec::new(),
+            segment: Vec::new(),
+            in_speech: false,
+            speech_frames: 0,
+        }
+    }
+
+    /// True while an utterance is in progress.
+    pub fn in_speech(&self) -> bool {
+        self.in_speech
+    }
+
+    /// Milliseconds since the last frame of speech, or since the start if there was none.
+    pub fn silence_ms(&self) -> usize {
+        self.vad.silence_ms()
+    }
+
+    /// Feed samples and append every utterance that ended to `segments`.
+    pub fn push(&mut self, samples: &[f32], segments: &mut Vec<Vec<f32>>) {
+        let mut partial = std::mem::take(&mut self.partial);
+        for_each_frame(&mut partial, samples, |frame| {
+            self.push_frame(frame, segments)
+        });
+        self.partial = partial;
+    }
+
+    fn push_frame(&mut self, frame: &[f32], segments: &mut Vec<Vec<f32>>) {
+        let is_speech = self.vad.classify(frame);
+
+        if !self.in_speech {
+            if is_speech {
+                self.in_speech = true;
+                self.speech_frames = 1;
+                self.segment.append(&mut self.lead);
+                self.segment.extend_from_slice(frame);
+            } else {
+                self.lead.extend_from_slice(frame);
+                let excess = self.lead.len().saturating_sub(self.padding);
+                self.lead.drain(..excess);
+            }
+            return;
+        }
+
+        self.segment.extend_from_slice(frame);
+        if is_speech {
+            self.speech_frames += 1;
+        }
+
+        let silent_frames = self.vad.silence_ms() / FRAME_MS;
+        if silent_frames >= self.pause_frames {
+            // Keep `padding` of the pause, hand the rest on as lead-in for the next one.
+            // A pause that began before a `max_len` cut is only partly in this segment.
+            let silence = (silent_frames * FRAME_LEN).min(self.segment.len());
+            let keep = self.segment.len() - silence + self.padding.min(silence);
+            self.end_segment(keep, segments);
+        } else if self.segment.len() >= self.max_len {
+            let keep = self.segment.len();
+            self.end_segment(keep, segments);
+            // The utterance carries on into the next segment
+            self.in_speech = true;
+        }
+    }
+
+    fn end_segment(&mut self, keep: usize, segments: &mut Vec<Vec<f32>>) {
+        self.lead = self.segment.split_off(keep);
+        let excess = self.lead.len().saturating_sub(self.padding);
+        self.lead.drain(..excess);
+
+        let segment = std::mem::take(&mut self.segment);
+        if self.speech_frames * FRAME_MS >= MIN_SPEECH_MS {
+            segments.push(segment);
+        }
+        self.in_speech = false;
+        self.speech_frames = 0;
+    }
+
+    /// End of the stream: return the utterance in progress, if it holds enough speech.
+    pub fn finish(&mut self) -> Option<Vec<f32>> {
+        let partial = std::mem::take(&mut self.partial);
+        let mut segments = Vec::new();
+        if self.in_speech {
+            self.segment.extend_from_slice(&partial);
+            let keep = self.segment.len();
+            self.end_segment(keep, &mut segments);
+        }
+        self.reset();
+        segments.pop()
+    }
+
+    /// Forget everything, including the noise floor.
+    pub fn reset(&mut self) {
+        self.vad.reset();
+        self.partial.clear();
+        self.lead.clear();
+        self.segment.clear();
+        self.in_speech = false;
+        self.speech_frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn silence(ms: usize) -> Vec<f32> {
+        vec![0.0; SAMPLE_RATE as usize * ms / 1000]
+    }
+
+    /// Deterministic low-level noise, roughly `amplitude` peak.
+    fn noise(ms: usize, amplitude: f32) -> Vec<f32> {
+        let mut state = 0x1234_5678u32;
+        (0..SAMPLE_RATE as usize * ms / 1000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    /// A tone with a syllable-like on/off envelope, standing in for speech.
+    fn speech(ms: usize) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize * ms / 1000)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let envelope = 0.6 + 0.4 * (2.0 * PI * 4.0 * t).sin();
+                0.3 * envelope * (2.0 * PI * 220.0 * t).sin()
+            })
+            .collect()
+    }
+
+    fn concat(parts: &[Vec<f32>]) -> Vec<f32> {
+        parts.concat()
+    }
+
+    fn ms(samples: usize) -> usize {
+        samples * 1000 / SAMPLE_RATE as usize
+    }
+
+    #[test]
+    fn test_level_db() {
+        assert_eq!(level_db(&[]), -100.0);
+        assert_eq!(level_db(&[0.0; 10]), -100.0);
+        assert!(level_db(&[1.0; 10]).abs() < 1e-4);
+        assert!((level_db(&[0.1; 10]) + 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_silence_has_no_speech() {
+        assert_eq!(speech_range(&silence(1000)), None);
+        assert_eq!(speech_range(&noise(1000, 0.0005)), None);
+        assert_eq!(speech_range(&[]), None);
+    }
+
+    #[test]
+    fn test_steady_room_noise_has_no_speech() {
+        // Uniform noise peaking at 0.01 sits around -45 dBFS
+        let samples = noise(3000, 0.01);
+        assert!((-46.0..-44.0).contains(&level_db(&samples)));
+        assert_eq!(speech_threshold(&frame_levels(&samples)), None);
+        assert_eq!(speech_range(&samples), None);
+    }
+
+    #[test]
+    fn test_trims_leading_and_trailing_silence() {
+        let samples = concat(&[noise(800, 0.0005), speech(1000), noise(1200, 0.0005)]);
+        let range = speech_range(&samples).unwrap();
+
+        // Speech spans 800..1800 ms; expect it back with about PADDING_MS either side
+        let start = ms(range.start);
+        let end = ms(range.end);
+        assert!((550..=800).contains(&start), "start {} ms", start);
+        assert!((1800..=2050).contains(&end), "end {} ms", end);
+    }
+
+    #[test]
+    fn test_all_speech_kept() {
+        let samples = speech(1500);
+        assert_eq!(speech_range(&samples), Some(0..samples.len()));
+    }
+
+    #[test]
+    fn test_click_is_not_speech() {
+        let mut samples = silence(1000);
+        for s in &mut samples[8000..8040] {
+            *s = 0.8;
+        }
+        assert_eq!(speech_range(&samples), None);
+    }
+
+    #[test]
+    fn test_quiet_speech_over_noise() {
+        let quiet: Vec<f32> = speech(600).iter().map(|s| s * 0.1).collect();
+        let samples = concat(&[noise(500, 0.001), quiet, noise(500, 0.001)]);
+        let range = speech_range(&samples).unwrap();
+        assert!(ms(range.start) <= 500 && ms(range.end) >= 1100);
+    }
+
+    fn segment_all(segmenter: &mut Segmenter, samples: &[f32]) -> Vec<Vec<f32>> {
+        let mut segments = Vec::new();
+        // Feed in odd-sized chunks like the capture loop does
+        for chunk in samples.chunks(333) {
+            segmenter.push(chunk, &mut segments);
+        }
+        segments.extend(segmenter.finish());
+        segments
+    }
+
+    #[test]
+    fn test_segmenter_splits_at_pauses() {
+        let samples = concat(&[
+            noise(500, 0.0005),
+            speech(1000),
+            noise(1000, 0.0005),
+            speech(700),
+            noise(1000, 0.0005),
+        ]);
+        let mut segmenter = Segmenter::new(600, SAMPLE_RATE as usize * 30);
+        let segments = segment_all(&mut segmenter, &samples);
+
+        assert_eq!(segments.len(), 2);
+        // Each segment is its speech plus at most PADDING_MS on either side
+        for (segment, speech_ms) in segments.iter().zip([1000, 700]) {
+            let len = ms(segment.len());
+            assert!(
+                len >= speech_ms && len <= speech_ms + 2 * PADDING_MS + FRAME_MS,
+                "segment {} ms for {} ms of speech",
+                len,
+                speech_ms
+            );
+        }
+    }
+
+    #[test]
+    fn test_segmenter_splits_over_room_noise() {
+        // A -45 dBFS room from the first frame, with no quiet stretch to learn from
+        let room = |ms| noise(ms, 0.01);
+        let samples = concat(&[room(500), speech(1000), room(1000), speech(700), room(1000)]);
+        let mut segmenter = Segmenter::new(600, SAMPLE_RATE as usize * 30);
+        let segments = segment_all(&mut segmenter, &samples);
+
+        assert_eq!(segments.len(), 2);
+        for (segment, speech_ms) in segments.iter().zip([1000, 700]) {
+            let len = ms(segment.len());
+            assert!(
+                len >= speech_ms && len <= speech_ms + 2 * PADDING_MS + FRAME_MS,
+                "segment {} ms for {} ms of speech",
+                len,
+                speech_ms
+            );
+        }
+
+        // Nothing but the room is nothing at all
+        segmenter.reset();
+        assert!(segment_all(&mut segmenter, &room(5000)).is_empty());
+    }
+
+    #[test]
+    fn test_segmenter_short_pause_does_not_split() {
+        let samples = concat(&[
+            noise(500, 0.0005),
+            speech(800),
+            noise(300, 0.0005),
+            speech(800),
+            noise(1000, 0.0005),
+        ]);
+        let mut segmenter = Segmenter::new(600, SAMPLE_RATE as usize * 30);
+        assert_eq!(segment_all(&mut segmenter, &samples).len(), 1);
+    }
+
+    #[test]
+    fn test_segmenter_ignores_silence_and_clicks() {
+        let mut samples = noise(3000, 0.0005);
+        for s in &mut samples[16000..16040] {
+            *s = 0.8;
+        }
+        let mut segmenter = Segmenter::new(600, SAMPLE_RATE as usize * 30);
+        assert!(segment_all(&mut segmenter, &samples).is_empty());
+    }
+
+    #[test]
+    fn test_segmenter_forces_cut_at_max_len() {
+        let samples = concat(&[noise(300, 0.0005), speech(5000), noise(1000, 0.0005)]);
+        let max_len = SAMPLE_RATE as usize * 2;
+        let mut segmenter = Segmenter::new(600, max_len);
+        let segments = segment_all(&mut segmenter, &samples);
+
+        assert_eq!(segments.len(), 3);
+        assert!(segments.iter().all(|s| s.len() <= max_len + FRAME_LEN));
+    }
+
+    #[test]
+    fn test_segmenter_pause_across_max_len_cut() {
+        // The segment (lead-in plus speech) reaches max_len a few frames into the pause
+        let samples = concat(&[noise(300, 0.0005), speech(1590), noise(1500, 0.0005)]);
+        let max_len = SAMPLE_RATE as usize * 2;
+        let mut segmenter = Segmenter::new(600, max_len);
+        let segments = segment_all(&mut segmenter, &samples);
+
+        assert_eq!(segments.len(), 1);
+        assert!(segments[0].len() <= max_len);
+        assert!(!segmenter.in_speech());
+    }
+
+    #[test]
+    fn test_segmenter_finish_returns_open_utterance() {
+        let samples = concat(&[noise(300, 0.0005), speech(800)]);
+        let mut segmenter = Segmenter::new(600, SAMPLE_RATE as usize * 30);
+        let mut segments = Vec::new();
+        segmenter.push(&samples, &mut segments);
+        assert!(segments.is_empty());
+        assert!(segmenter.in_speech());
+
+        let segment = segmenter.finish().unwrap();
+        assert!(ms(segment.len()) >= 800);
+        assert!(!segmenter.in_speech());
+        assert_eq!(segmenter.finish(), None);
+    }
+
+    #[test]
+    fn test_segmenter_silence_ms() {
+        let mut segmenter = Segmenter::new(600, SAMPLE_RATE as usize * 30);
+        let mut segments = Vec::new();
+        // The noise floor is learned from the quiet start
+        segmenter.push(&noise(300, 0.0005), &mut segments);
+        segmenter.push(&speech(500), &mut segments);
+        assert_eq!(segmenter.silence_ms(), 0);
+        segmenter.push(&silence(300), &mut segments);
+        assert!((270..=300).contains(&segmenter.silence_ms()));
+    }
+
+    #[test]
+    fn test_live_vad_tracks_trailing_silence() {
+        let mut vad = LiveVad::new();
+        vad.push(&noise(500, 0.0005));
+        assert!(!vad.heard_speech());
+        assert!(vad.silence_ms() >= 450);
+
+        vad.push(&speech(600));
+        assert!(vad.heard_speech());
+        assert!(vad.silence_ms() < FRAME_MS * 2);
+
+        vad.push(&noise(900, 0.0005));
+        assert!((840..=900).contains(&vad.silence_ms()));
+
+        vad.reset();
+        assert!(!vad.heard_speech());
+        assert_eq!(vad.silence_ms(), 0);
+    }
+
+    #[test]
+    fn test_auto_stop_after_silence() {
+        let auto_stop = AutoStop {
+            silence_ms: 1000,
+            max_len: 0,
+        };
+        let mut vad = LiveVad::new();
+
+        // Silence before any speech doesn't count
+        vad.push(&noise(1500, 0.0005));
+        assert_eq!(auto_stop.reason(&vad, 0), None);
+
+        vad.push(&speech(800));
+        vad.push(&noise(600, 0.0005));
+        assert_eq!(auto_stop.reason(&vad, 0), None);
+
+        vad.push(&noise(500, 0.0005));
+        assert!(auto_stop.reason(&vad, 0).unwrap().contains("silence"));
+    }
+
+    #[test]
+    fn test_auto_stop_after_silence_in_noisy_room() {
+        let auto_stop = AutoStop {
+            silence_ms: 1000,
+            max_len: 0,
+        };
+        let room = |ms| noise(ms, 0.01);
+        let mut vad = LiveVad::new();
+
+        vad.push(&room(3000));
+        assert!(!vad.heard_speech());
+        assert_eq!(auto_stop.reason(&vad, 0), None);
+
+        vad.push(&speech(800));
+        vad.push(&room(600));
+        assert_eq!(auto_stop.reason(&vad, 0), None);
+
+        vad.push(&room(500));
+        assert!(auto_stop.reason(&vad, 0).unwrap().contains("silence"));
+
+        // The next recording learns the room again
+        vad.reset();
+        vad.push(&room(2000));
+        assert_eq!(auto_stop.reason(&vad, 0), None);
+    }
+
+    #[test]
+    fn test_auto_stop_max_length() {
+        let auto_stop = AutoStop {
+            silence_ms: 0,
+            max_len: SAMPLE_RATE as usize * 60,
+        };
+        let vad = LiveVad::new();
+        assert_eq!(auto_stop.reason(&vad, SAMPLE_RATE as usize * 59), None);
+        assert_eq!(
+            auto_stop.reason(&vad, SAMPLE_RATE as usize * 60).as_deref(),
+            Some("Recording reached 60s")
+        );
+    }
+
+    #[test]
+    fn test_auto_stop_disabled() {
+        let auto_stop = AutoStop {
+            silence_ms: 0,
+            max_len: 0,
+        };
+        let mut vad = LiveVad::new();
+        vad.push(&speech(500));
+        vad.push(&silence(5000));
+        assert_eq!(auto_stop.reason(&vad, usize::MAX), None);
+    }
+}
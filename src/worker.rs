@@ -0,0 +1,495 @@
+//! Transcription worker. The engine lives on its own thread and handles recordings one
+//! at a time, in the order they were queued, typing each result as it finishes.
+
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+
+use crate::audio::write_wav_to;
+use crate::dictionary::Dictionary;
+use crate::engine::Transcriber;
+use crate::{is_typeable, render_input, spoken, type_text, typed_len, vad, Input, SAMPLE_RATE};
+
+/// Delivers a transcription as text and key presses, normally by typing it. Returns
+/// whether all of it was delivered.
+pub type OutputFn = Box<dyn FnMut(&[Input]) -> bool + Send>;
+
+/// Loads the engine again after it was unloaded.
+pub type LoadFn = Arc<dyn Fn() -> Result<Box<dyn Transcriber>, String> + Send + Sync>;
+
+/// One recording to transcribe and type.
+pub struct Job {
+    /// Shared with the queue while transcribing, so shutdown can save it without a copy.
+    samples: Arc<Vec<f32>>,
+    /// Gets the typed text, or why nothing was typed.
+    done: Option<Sender<Result<String, String>>>,
+}
+
+impl Job {
+    pub fn new(samples: Vec<f32>) -> Self {
+        Job {
+            samples: Arc::new(samples),
+            done: None,
+        }
+    }
+
+    pub fn with_reply(samples: Vec<f32>, done: Sender<Result<String, String>>) -> Self {
+        Job {
+            samples: Arc::new(samples),
+            done: Some(done),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    /// Audio of the job being transcribed, kept in case shutdown has to give up on it.
+    current: Option<Arc<Vec<f32>>>,
+    /// Audio of jobs that failed because the engine couldn't be loaded, to be saved.
+    failed: Vec<Arc<Vec<f32>>>,
+    /// Engine to switch to before the next job.
+    next_engine: Option<Box<dyn Transcriber>>,
+    loader: Option<LoadFn>,
+    dictionary: Arc<Dictionary>,
+    spoken_commands: bool,
+    /// Drop the engine once nothing is queued.
+    unload: bool,
+    /// Load the engine now rather than when the next job arrives.
+    preload: bool,
+    loaded: bool,
+    loading: bool,
+    /// No more jobs will be queued; the thread exits once the queue is empty.
+    closed: bool,
+    /// Shutdown gave up waiting, so nothing more may be typed.
+    abandoned: bool,
+}
+
+impl Queue {
+    fn is_busy(&self) -> bool {
+        self.current.is_some() || !self.jobs.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub struct Worker {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    /// Move `engine` onto a new worker thread that types what it transcribes.
+    pub fn spawn(engine: Box<dyn Transcriber>, language: Option<String>, use_vad: bool) -> Self {
+        Self::spawn_with(engine, language, Box::new(type_text), use_vad)
+    }
+
+    /// Like `spawn`, handing the text to `output` instead of typing it.
+    pub fn spawn_with(
+        engine: Box<dyn Transcriber>,
+        language: Option<String>,
+        output: OutputFn,
+        use_vad: bool,
+    ) -> Self {
+        let shared = Arc::new(Shared::default());
+        shared.lock().loaded = true;
+        let worker_shared = Arc::clone(&shared);
+        let handle = thread::Builder::new()
+            .name("transcribe".to_string())
+            .spawn(move || run(&worker_shared, engine, language, output, use_vad))
+            .expect("failed to spawn transcription worker");
+        Worker {
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    /// Queue a recording behind any that are already waiting.
+    pub fn submit(&self, job: Job) {
+        let mut queue = self.shared.lock();
+        if queue.closed {
+            return;
+        }
+        queue.jobs.push_back(job);
+        self.shared.changed.notify_all();
+    }
+
+    /// Switch to `engine` once the job in progress, if any, is done. Jobs queued after
+    /// it use the new engine.
+    pub fn set_engine(&self, engine: Box<dyn Transcriber>) {
+        self.shared.lock().next_engine = Some(engine);
+        self.shared.changed.notify_all();
+    }
+
+    /// How to load the engine again after `unload`. Without one, `unload` does nothing.
+    pub fn set_loader(&self, loader: LoadFn) {
+        self.shared.lock().loader = Some(loader);
+    }
+
+    /// Rewrite transcriptions with `dictionary` before they're typed, from the next job on.
+    pub fn set_dictionary(&self, dictionary: Dictionary) {
+        self.shared.lock().dictionary = Arc::new(dictionary);
+    }
+
+    /// Turn spoken punctuation and commands into symbols and keys, from the next job on.
+    pub fn set_spoken_commands(&self, enabled: bool) {
+        self.shared.lock().spoken_commands = enabled;
+    }
+
+    /// Free the engine's memory once nothing is queued. It's loaded again on `preload`
+    /// or when the next job arrives.
+    pub fn unload(&self) {
+        self.shared.lock().unload = true;
+        self.shared.changed.notify_all();
+    }
+
+    /// Start loading an unloaded engine in the background, e.g. while the user talks.
+    pub fn preload(&self) {
+        self.shared.lock().preload = true;
+        self.shared.changed.notify_all();
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.shared.lock().loaded
+    }
+
+    /// True while an unloaded engine is being loaded again.
+    pub fn is_loading(&self) -> bool {
+        self.shared.lock().loading
+    }
+
+    /// True while any job is queued or being transcribed.
+    pub fn is_busy(&self) -> bool {
+        self.shared.lock().is_busy()
+    }
+
+    /// Take the audio of jobs that failed because the engine couldn't be loaded again,
+    /// so it can be saved instead of lost.
+    pub fn take_failed(&self) -> Vec<Vec<f32>> {
+        self.shared
+            .lock()
+            .failed
+            .drain(..)
+            .map(Arc::unwrap_or_clone)
+            .collect()
+    }
+
+    /// Queue a recording and wait until it has been transcribed and typed.
+    pub fn transcribe(&self, samples: Vec<f32>) -> Result<String, String> {
+        let (tx, rx) = mpsc::channel();
+        self.submit(Job::with_reply(samples, tx));
+        rx.recv()
+            .unwrap_or_else(|_| Err("transcription worker stopped".to_string()))
+    }
+
+    /// Finish the queued jobs and stop the thread, waiting at most `deadline`. Returns
+    /// the audio of the jobs that didn't finish in time, and of those `take_failed` would
+    /// return; none of it will ever be typed.
+    pub fn shutdown(mut self, deadline: Duration) -> Vec<Vec<f32>> {
+        let give_up = Instant::now() + deadline;
+        let mut queue = self.shared.lock();
+        queue.closed = true;
+        self.shared.changed.notify_all();
+
+        while queue.is_busy() {
+            let now = Instant::now();
+            if now >= give_up {
+                // The engine can't be interrupted, so the thread is left to die with
+                // the process
+                queue.abandoned = true;
+                let failed = std::mem::take(&mut queue.failed);
+                return failed
+                    .into_iter()
+                    .chain(queue.current.take())
+                    .chain(queue.jobs.drain(..).map(|job| job.samples))
+                    .map(Arc::unwrap_or_clone)
+                    .collect();
+            }
+            queue = self
+                .shared
+                .changed
+                .wait_timeout(queue, give_up - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        drop(queue);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        self.take_failed()
+    }
+}
+
+fn run(
+    shared: &Shared,
+    engine: Box<dyn Transcriber>,
+    language: Option<String>,
+    mut output: OutputFn,
+    use_vad: bool,
+) {
+    let mut engine = Some(engine);
+    // Why the last load failed, reported to the next job instead of retrying at once
+    let mut load_error = None;
+    // Characters the last job typed, for "scratch that". Zero unless it typed them all.
+    let mut last_typed = 0;
+
+    loop {
+        let (job, dictionary, spoken_commands) = {
+            let mut queue = shared.lock();
+            loop {
+                if queue.abandoned {
+                    return;
+                }
+                if let Some(next) = queue.next_engine.take() {
+                    queue.loaded = true;
+                    load_error = None;
+                    // Free the old model without holding the lock, it can take a while
+                    let old = engine.replace(next);
+                    drop(queue);
+                    drop(old);
+                    queue = shared.lock();
+                    continue;
+                }
+                if std::mem::take(&mut queue.unload)
+                    && engine.is_some()
+                    && queue.jobs.is_empty()
+                    && queue.loader.is_some()
+                {
+                    queue.loaded = false;
+                    let old = engine.take();
+                    drop(queue);
+                    drop(old);
+                    info!("Model unloaded");
+                    queue = shared.lock();
+                    continue;
+                }
+                let wanted = std::mem::take(&mut queue.preload) || !queue.jobs.is_empty();
+                if engine.is_none() && wanted && load_error.is_none() {
+                    if let Some(loader) = queue.loader.clone() {
+                        queue.loading = true;
+                        drop(queue);
+                        info!("Loading the model again...");
+                        match loader() {
+                            Ok(loaded) => {
+                                info!("Model loaded successfully");
+                                engine = Some(loaded);
+                            }
+                            Err(e) => {
+                                error!("Failed to load model: {}", e);
+                                load_error = Some(e);
+                            }
+                        }
+                        queue = shared.lock();
+                        queue.loading = false;
+                        queue.loaded = engine.is_some();
+                        shared.changed.notify_all();
+                        continue;
+                    }
+                }
+                if let Some(job) = queue.jobs.pop_front() {
+                    queue.current = Some(Arc::clone(&job.samples));
+                    break (job, Arc::clone(&queue.dictionary), queue.spoken_commands);
+                }
+                if queue.closed {
+                    return;
+                }
+                queue = shared
+                    .changed
+                    .wait(queue)
+                    .unwrap_or_else(|e| e.into_inner());
+            }
+        };
+
+        let result = match engine.as_mut() {
+            Some(engine) => process(engine.as_mut(), &job.samples, language.as_deref(), use_vad)
+                .map(|text| apply_dictionary(&dictionary, text)),
+            None => Err(format!(
+                "failed to load model: {}",
+                load_error.take().unwrap_or_else(|| "no loader".to_string())
+            )),
+        };
+
+        {
+            let mut queue = shared.lock();
+            if queue.abandoned {
+                return;
+            }
+            // From here shutdown waits for the thread instead of saving the audio
+            queue.current = None;
+            if engine.is_none() {
+                // Never transcribed, so hand the audio back to be saved
+                queue.failed.push(Arc::clone(&job.samples));
+            }
+            shared.changed.notify_all();
+        }
+
+        let result = result.map(|text| {
+            let inputs = if spoken_commands {
+                spoken::interpret(&text, last_typed)
+            } else {
+                vec![Input::Text(text)]
+            };
+            last_typed = if !is_typeable(&inputs) {
+                warn!("Empty transcription, skipping");
+                0
+            } else if output(&inputs) {
+                typed_len(&inputs)
+            } else {
+                0
+            };
+            render_input(&inputs)
+        });
+        if result.is_err() {
+            last_typed = 0;
+        }
+        if let Some(done) = job.done {
+            let _ = done.send(result);
+        }
+    }
+}
+
+fn process(
+    engine: &mut dyn Transcriber,
+    samples: &[f32],
+    language: Option<&str>,
+    use_vad: bool,
+) -> Result<String, String> {
+    if samples.is_empty() {
+        warn!("No audio recorded");
+        return Err("no audio recorded".to_string());
+    }
+
+    // The engine takes its own copy, of only the speech when trimming
+    let samples = if use_vad {
+        trim_silence(samples)?
+    } else {
+        samples.to_vec()
+    };
+
+    info!(
+        "Transcribing {} samples ({:.2}s)...",
+        samples.len(),
+        samples.len() as f32 / SAMPLE_RATE as f32
+    );
+
+    match engine.transcribe(samples, language) {
+        Ok(transcription) => {
+            let text = transcription.text.trim();
+            info!("Transcription: {}", text);
+            Ok(text.to_string())
+        }
+        Err(e) => {
+            error!("Transcription failed: {}", e);
+            Err(format!("transcription failed: {}", e))
+        }
+    }
+}
+
+fn apply_dictionary(dictionary: &Dictionary, text: String) -> String {
+    if dictionary.is_empty() {
+        return text;
+    }
+    let replaced = dictionary.apply(&text);
+    if replaced != text {
+        info!("After dictionary: {}", replaced);
+    }
+    replaced
+}
+
+/// Cut leading and trailing silence so the engine doesn't hallucinate on it. Fails
+/// when there's no speech at all.
+fn trim_silence(samples: &[f32]) -> Result<Vec<f32>, String> {
+    let secs = |n: usize| n as f32 / SAMPLE_RATE as f32;
+    let Some(range) = vad::speech_range(samples) else {
+        info!(
+            "No speech detected in {:.2}s of audio, skipping transcription",
+            secs(samples.len())
+        );
+        return Err("no speech detected".to_string());
+    };
+
+    let leading = range.start;
+    let trailing = samples.len() - range.end;
+    if leading > 0 || trailing > 0 {
+        info!(
+            "Trimmed {:.2}s of leading and {:.2}s of trailing silence",
+            secs(leading),
+            secs(trailing)
+        );
+    }
+    Ok(samples[range].to_vec())
+}
+
+/// Recordings saved by this process, to number the next one.
+static SAVED: AtomicUsize = AtomicUsize::new(0);
+
+/// Save recordings that couldn't be transcribed as WAV files in `dir`, so they can be
+/// transcribed later. Returns the files written.
+pub fn save_recordings(dir: &Path, recordings: &[Vec<f32>]) -> Vec<PathBuf> {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        error!("Failed to create {:?}: {}", dir, e);
+        return Vec::new();
+    }
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut saved = Vec::new();
+    for samples in recordings {
+        // Numbered across calls, and never over an existing file, in case an earlier
+        // save or another run used the same second
+        let (path, file) = loop {
+            let n = SAVED.fetch_add(1, Ordering::Relaxed) + 1;
+            let path = dir.join(format!("hammertalk-{}-{}.wav", stamp, n));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                result => break (path, result),
+            }
+        };
+        let result = file
+            .map_err(hound::Error::from)
+            .and_then(|file| write_wav_to(BufWriter::new(file), samples));
+        match result {
+            Ok(()) => {
+                info!("Saved untranscribed recording to {:?}", path);
+                saved.push(path);
+            }
+            Err(e) => error!("Failed to save recording to {:?}: {}", path, e),
+        }
+    }
+    saved
+}
+
+/// Stop `worker` for shutdown, waiting at most `deadline`, and save the audio of the
+/// jobs it didn't finish in `dir`. Returns the files saved.
+pub fn shut_down(worker: Worker, deadline: Duration, dir: &Path) -> Vec<PathBuf> {
+    let unfinished = worker.shutdown(deadline);
+    if unfinished.is_empty() {
+        return Vec::new();
+    }
+    // Either the deadline passed, or the model failed to load again for the last jobs
+    warn!(
+        "{} recording(s) were not transcribed before shutdown",
+        unfinished.len()
+    );
+    save_recordings(dir, &unfinished)
+}
@@ -0,0 +1,246 @@
+//! Downloading, verifying and removing models, against a local HTTP stand-in for
+//! huggingface.co.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use hammertalk::models::{
+    self, check_installed, download_file, model_for, status, verify, ModelFile, Status, Verdict,
+    CHECKSUMS_FILE,
+};
+use hammertalk::EngineChoice;
+use tempfile::tempdir;
+
+/// Serves `files` by URL path and counts the requests. Anything else is a 404.
+struct Server {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+fn serve(files: HashMap<String, Vec<u8>>) -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&requests);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            // Skip the headers
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                line.clear();
+            }
+
+            let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+            seen.lock().unwrap().push(path.to_string());
+            let response = match files.get(path) {
+                Some(body) => {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(body);
+                    response
+                }
+                None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_vec(),
+            };
+            let _ = stream.write_all(&response);
+        }
+    });
+
+    Server { url, requests }
+}
+
+/// The server-side path of each of `model`'s files, with some content for each.
+fn model_files(model: &models::Model) -> HashMap<String, Vec<u8>> {
+    model
+        .files
+        .iter()
+        .map(|file| {
+            let path = file.url.strip_prefix("https://huggingface.co").unwrap();
+            (
+                path.to_string(),
+                format!("contents of {}", file.path).into_bytes(),
+            )
+        })
+        .collect()
+}
+
+fn download(model: &models::Model, dir: &Path, mirror: &str) -> Result<(), String> {
+    for file in model.files {
+        download_file(file, dir, Some(mirror))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_download_then_verify() {
+    let model = model_for(&EngineChoice::MoonshineTiny);
+    let server = serve(model_files(model));
+    let temp = tempdir().unwrap();
+    let dir = temp.path();
+    assert!(check_installed(model, dir).is_err());
+
+    download(model, dir, &server.url).unwrap();
+
+    assert_eq!(status(model, dir), Status::Installed);
+    assert!(check_installed(model, dir).is_ok());
+    assert_eq!(
+        fs::read_to_string(dir.join("moonshine-tiny/tokenizer.json")).unwrap(),
+        "contents of moonshine-tiny/tokenizer.json"
+    );
+    for (_, verdict) in verify(model, dir).unwrap() {
+        assert_eq!(verdict, Verdict::Ok);
+    }
+    assert_eq!(
+        fs::read_to_string(dir.join(CHECKSUMS_FILE))
+            .unwrap()
+            .lines()
+            .count(),
+        model.files.len()
+    );
+}
+
+#[test]
+fn test_download_skips_existing_files() {
+    let model = model_for(&EngineChoice::WhisperTiny);
+    let server = serve(model_files(model));
+    let temp = tempdir().unwrap();
+
+    download(model, temp.path(), &server.url).unwrap();
+    download(model, temp.path(), &server.url).unwrap();
+
+    assert_eq!(server.requests.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_verify_catches_corruption() {
+    let model = model_for(&EngineChoice::WhisperBaseEn);
+    let server = serve(model_files(model));
+    let temp = tempdir().unwrap();
+    download(model, temp.path(), &server.url).unwrap();
+
+    fs::write(temp.path().join("ggml-base.en.bin"), "flipped bits").unwrap();
+
+    let results = verify(model, temp.path()).unwrap();
+    assert!(matches!(results[0].1, Verdict::Mismatch(_)));
+}
+
+#[test]
+fn test_download_replaces_corrupt_file() {
+    let model = model_for(&EngineChoice::WhisperTiny);
+    let server = serve(model_files(model));
+    let temp = tempdir().unwrap();
+    let path = temp.path().join("ggml-tiny.bin");
+    download(model, temp.path(), &server.url).unwrap();
+
+    fs::write(&path, "flipped bits").unwrap();
+    download(model, temp.path(), &server.url).unwrap();
+
+    assert_eq!(server.requests.lock().unwrap().len(), 2);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "contents of ggml-tiny.bin"
+    );
+}
+
+#[test]
+fn test_pinned_checksum_rejects_wrong_bytes() {
+    // SHA-256 of "abc"
+    let file = ModelFile {
+        path: "pinned.bin",
+        url: "https://huggingface.co/example/resolve/main/pinned.bin",
+        sha256: Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+    };
+    let temp = tempdir().unwrap();
+    let dir = temp.path();
+
+    let tampered = serve(HashMap::from([(
+        "/example/resolve/main/pinned.bin".to_string(),
+        b"abd".to_vec(),
+    )]));
+    let err = download_file(&file, dir, Some(&tampered.url)).unwrap_err();
+    assert!(err.contains("checksum mismatch"), "{}", err);
+    assert!(!dir.join("pinned.bin").exists());
+    assert!(!dir.join("pinned.bin.part").exists());
+    assert!(!dir.join(CHECKSUMS_FILE).exists());
+
+    let genuine = serve(HashMap::from([(
+        "/example/resolve/main/pinned.bin".to_string(),
+        b"abc".to_vec(),
+    )]));
+    assert_eq!(download_file(&file, dir, Some(&genuine.url)), Ok(true));
+    assert_eq!(fs::read_to_string(dir.join("pinned.bin")).unwrap(), "abc");
+}
+
+#[test]
+fn test_check_installed_rejects_corrupt_file() {
+    let model = model_for(&EngineChoice::WhisperSmallEn);
+    let server = serve(model_files(model));
+    let temp = tempdir().unwrap();
+    download(model, temp.path(), &server.url).unwrap();
+    assert!(check_installed(model, temp.path()).is_ok());
+
+    fs::write(temp.path().join("ggml-small.en.bin"), "flipped bits").unwrap();
+
+    let err = check_installed(model, temp.path()).unwrap_err();
+    assert!(err.contains("does not match its checksum"), "{}", err);
+    assert!(
+        err.contains("hammertalk models download whisper-small-en"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_failed_download_leaves_nothing_behind() {
+    let model = model_for(&EngineChoice::MoonshineBase);
+    let mut files = model_files(model);
+    // The tokenizer comes from a different repo, which the mirror doesn't have
+    files.retain(|path, _| !path.ends_with("tokenizer.json"));
+    let server = serve(files);
+    let temp = tempdir().unwrap();
+    let dir = temp.path();
+
+    let err = download(model, dir, &server.url).unwrap_err();
+    assert!(err.contains("404"), "{}", err);
+
+    assert_eq!(
+        status(model, dir),
+        Status::Partial(vec!["moonshine-base/tokenizer.json"])
+    );
+    assert!(!dir.join("moonshine-base/tokenizer.json.part").exists());
+    let err = check_installed(model, dir).unwrap_err();
+    assert!(err.contains("moonshine-base/tokenizer.json"), "{}", err);
+    assert!(
+        err.contains("hammertalk models download moonshine-base"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_remove_after_download() {
+    let model = model_for(&EngineChoice::ParakeetTdtV3Int8);
+    let server = serve(model_files(model));
+    let temp = tempdir().unwrap();
+    download(model, temp.path(), &server.url).unwrap();
+
+    models::remove(model, temp.path()).unwrap();
+
+    assert_eq!(status(model, temp.path()), Status::Missing);
+    assert!(!temp.path().join("parakeet-tdt-v3-int8").exists());
+}
@@ -0,0 +1,627 @@
+//! The transcription worker and its shutdown, using a mock engine.
+
+use std::env;
+use std::error::Error;
+use std::panic;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hammertalk::audio::write_wav;
+use hammertalk::control::{self, Command};
+use hammertalk::dictionary::Dictionary;
+use hammertalk::engine::{self, Capabilities, Transcriber, Transcription};
+use hammertalk::worker::{self, Job, LoadFn, Worker};
+use hammertalk::{
+    default_model_path, get_pid_path, get_recovery_dir, get_socket_path, get_state_path,
+    render_input, write_pid_file, write_state, DaemonState, EngineChoice, Input, Key, RuntimeFiles,
+};
+use signal_hook::consts::SIGTERM;
+use tempfile::tempdir;
+
+/// "Transcribes" a recording as its length, optionally announcing each job on
+/// `started` and then waiting for a message on `gate`.
+#[derive(Default)]
+struct MockEngine {
+    delay: Duration,
+    started: Option<Sender<()>>,
+    gate: Option<Receiver<()>>,
+    /// Put in front of the text, to tell engines apart.
+    label: &'static str,
+}
+
+impl Transcriber for MockEngine {
+    fn load(&mut self, _path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn transcribe(
+        &mut self,
+        samples: Vec<f32>,
+        _language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error>> {
+        thread::sleep(self.delay);
+        if let Some(ref started) = self.started {
+            let _ = started.send(());
+        }
+        if let Some(ref gate) = self.gate {
+            let _ = gate.recv();
+        }
+        Ok(Transcription {
+            text: format!(" {}{} samples ", self.label, samples.len()),
+            segments: Vec::new(),
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// A worker around `engine` whose output collects the typed text instead of typing it.
+fn mock_worker(engine: MockEngine) -> (Worker, Arc<Mutex<Vec<String>>>) {
+    let typed = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::clone(&typed);
+    let worker = Worker::spawn_with(
+        Box::new(engine),
+        None,
+        Box::new(move |input: &[Input]| {
+            output.lock().unwrap().push(render_input(input));
+            true
+        }),
+        false,
+    );
+    (worker, typed)
+}
+
+/// A worker whose engine blocks until `release` gets a message, and tells `started`
+/// when it does.
+struct BlockedWorker {
+    worker: Worker,
+    started: Receiver<()>,
+    release: Sender<()>,
+    typed: Arc<Mutex<Vec<String>>>,
+}
+
+fn blocked_worker() -> BlockedWorker {
+    let (started_tx, started) = mpsc::channel();
+    let (release, gate) = mpsc::channel();
+    let (worker, typed) = mock_worker(MockEngine {
+        started: Some(started_tx),
+        gate: Some(gate),
+        ..Default::default()
+    });
+    BlockedWorker {
+        worker,
+        started,
+        release,
+        typed,
+    }
+}
+
+#[test]
+fn test_registered_engine_is_selectable_by_name() {
+    assert!("mock_engine".parse::<EngineChoice>().is_err());
+    engine::register("Mock_Engine", || {
+        Box::new(MockEngine {
+            label: "mock ",
+            ..Default::default()
+        })
+    })
+    .unwrap();
+    assert!(engine::registered().contains(&"mock-engine".to_string()));
+
+    let choice: EngineChoice = "mock-engine".parse().unwrap();
+    assert_eq!(choice, EngineChoice::Custom("mock-engine".to_string()));
+    assert_eq!(choice.to_string(), "mock-engine");
+    assert!(default_model_path(&choice).ends_with("mock-engine"));
+
+    let mut engine = engine::for_choice(&choice);
+    engine.load(Path::new("unused")).unwrap();
+    let text = engine.transcribe(vec![0.0; 3], None).unwrap().text;
+    assert_eq!(text, " mock 3 samples ");
+
+    // Built-in names can't be taken over
+    assert!(engine::register("whisper-base", || Box::new(MockEngine::default())).is_err());
+
+    // A custom engine nobody registered fails to load instead of panicking
+    let mut missing = engine::for_choice(&EngineChoice::Custom("nobody".to_string()));
+    assert!(missing.load(Path::new("unused")).is_err());
+}
+
+#[test]
+fn test_shutdown_finishes_queued_jobs_in_order() {
+    let (worker, typed) = mock_worker(MockEngine {
+        delay: Duration::from_millis(20),
+        ..Default::default()
+    });
+    worker.submit(Job::new(vec![0.0; 100]));
+    worker.submit(Job::new(vec![0.0; 200]));
+    worker.submit(Job::new(vec![0.0; 300]));
+
+    let unfinished = worker.shutdown(Duration::from_secs(5));
+
+    assert!(unfinished.is_empty());
+    assert_eq!(
+        *typed.lock().unwrap(),
+        vec!["100 samples", "200 samples", "300 samples"]
+    );
+}
+
+#[test]
+fn test_reply_carries_typed_text() {
+    let (worker, _typed) = mock_worker(MockEngine::default());
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(vec![0.0; 42], tx));
+    assert_eq!(rx.recv().unwrap(), Ok("42 samples".to_string()));
+
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(Vec::new(), tx));
+    assert!(rx.recv().unwrap().is_err());
+
+    worker.shutdown(Duration::from_secs(5));
+}
+
+#[test]
+fn test_room_noise_is_not_transcribed() {
+    let typed = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::clone(&typed);
+    let worker = Worker::spawn_with(
+        Box::new(MockEngine::default()),
+        None,
+        Box::new(move |input: &[Input]| {
+            output.lock().unwrap().push(render_input(input));
+            true
+        }),
+        true,
+    );
+
+    // Three seconds of steady noise around -45 dBFS, nobody talking
+    let mut state = 0x1234_5678u32;
+    let noise: Vec<f32> = (0..hammertalk::SAMPLE_RATE * 3)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * 0.01
+        })
+        .collect();
+    assert_eq!(
+        worker.transcribe(noise),
+        Err("no speech detected".to_string())
+    );
+
+    worker.shutdown(Duration::from_secs(5));
+    assert!(typed.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_dictionary_applies_to_typed_text_and_reply() {
+    let (worker, typed) = mock_worker(MockEngine::default());
+    let dictionary = Dictionary::parse("[replace]\n\"42 samples\" = \"forty-two\"\n").unwrap();
+    worker.set_dictionary(dictionary);
+
+    assert_eq!(
+        worker.transcribe(vec![0.0; 42]),
+        Ok("forty-two".to_string())
+    );
+    assert_eq!(worker.transcribe(vec![0.0; 7]), Ok("7 samples".to_string()));
+
+    worker.shutdown(Duration::from_secs(5));
+    assert_eq!(*typed.lock().unwrap(), vec!["forty-two", "7 samples"]);
+}
+
+#[test]
+fn test_spoken_commands_become_keys() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::clone(&inputs);
+    let worker = Worker::spawn_with(
+        Box::new(MockEngine {
+            label: "hi new line ",
+            ..Default::default()
+        }),
+        None,
+        Box::new(move |input: &[Input]| {
+            output.lock().unwrap().push(input.to_vec());
+            true
+        }),
+        false,
+    );
+    worker.set_spoken_commands(true);
+
+    assert_eq!(
+        worker.transcribe(vec![0.0; 3]),
+        Ok("hi\n3 samples".to_string())
+    );
+
+    worker.shutdown(Duration::from_secs(5));
+    assert_eq!(
+        *inputs.lock().unwrap(),
+        vec![vec![
+            Input::Text("hi".to_string()),
+            Input::Key(Key::Enter),
+            Input::Text("3 samples".to_string()),
+        ]]
+    );
+}
+
+#[test]
+fn test_scratch_that_erases_previous_job() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::clone(&inputs);
+    let worker = Worker::spawn_with(
+        Box::new(MockEngine {
+            label: "scratch that ",
+            ..Default::default()
+        }),
+        None,
+        Box::new(move |input: &[Input]| {
+            output.lock().unwrap().push(input.to_vec());
+            true
+        }),
+        false,
+    );
+
+    // Typed as is until spoken commands are on
+    assert_eq!(
+        worker.transcribe(vec![0.0; 1]),
+        Ok("scratch that 1 samples".to_string())
+    );
+    worker.set_spoken_commands(true);
+    worker.transcribe(vec![0.0; 2]).unwrap();
+
+    worker.shutdown(Duration::from_secs(5));
+    let inputs = inputs.lock().unwrap();
+    // The first job's text plus the space typed after it
+    let erased = inputs[1]
+        .iter()
+        .take_while(|i| **i == Input::Key(Key::Backspace))
+        .count();
+    assert_eq!(erased, "scratch that 1 samples ".len());
+    assert_eq!(inputs[1][erased..], [Input::Text("2 samples".to_string())]);
+}
+
+/// "Transcribes" each recording as the next of `texts`.
+struct ScriptedEngine {
+    texts: Vec<&'static str>,
+}
+
+impl Transcriber for ScriptedEngine {
+    fn load(&mut self, _path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn transcribe(
+        &mut self,
+        _samples: Vec<f32>,
+        _language: Option<&str>,
+    ) -> Result<Transcription, Box<dyn Error>> {
+        Ok(Transcription {
+            text: self.texts.remove(0).to_string(),
+            segments: Vec::new(),
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[test]
+fn test_scratch_that_only_erases_what_was_typed() {
+    let inputs = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::clone(&inputs);
+    let worker = Worker::spawn_with(
+        Box::new(ScriptedEngine {
+            texts: vec![
+                "hello there",
+                "   ",
+                "scratch that again",
+                "not typed",
+                "scratch that once more",
+            ],
+        }),
+        None,
+        // Typing fails for "not typed"
+        Box::new(move |input: &[Input]| {
+            output.lock().unwrap().push(input.to_vec());
+            render_input(input) != "not typed"
+        }),
+        false,
+    );
+    // Whitespace typed as is would still count as typed
+    worker.transcribe(vec![0.0; 1]).unwrap();
+    worker.transcribe(vec![0.0; 1]).unwrap();
+    worker.set_spoken_commands(true);
+    for _ in 0..3 {
+        worker.transcribe(vec![0.0; 1]).unwrap();
+    }
+
+    worker.shutdown(Duration::from_secs(5));
+    // The empty result isn't typed, and leaves nothing for "scratch that" to erase
+    assert_eq!(
+        *inputs.lock().unwrap(),
+        vec![
+            vec![Input::Text("hello there".to_string())],
+            vec![Input::Text("again".to_string())],
+            vec![Input::Text("not typed".to_string())],
+            vec![Input::Text("once more".to_string())],
+        ]
+    );
+}
+
+#[test]
+fn test_shutdown_deadline_returns_unfinished_audio() {
+    let BlockedWorker {
+        worker,
+        started,
+        release,
+        typed,
+    } = blocked_worker();
+    worker.submit(Job::new(vec![0.1; 10]));
+    started.recv().unwrap();
+    worker.submit(Job::new(vec![0.2; 20]));
+
+    let start = Instant::now();
+    let unfinished = worker.shutdown(Duration::from_millis(100));
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // The job being transcribed comes first, then the queued one
+    assert_eq!(unfinished, vec![vec![0.1; 10], vec![0.2; 20]]);
+
+    // Saved audio is never typed, even if the engine finishes afterwards
+    release.send(()).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert!(typed.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_sigterm_during_transcription_saves_audio_and_cleans_up() {
+    // The only test here that touches the environment or the process's signals
+    let runtime = tempdir().unwrap();
+    let state = tempdir().unwrap();
+    env::set_var("XDG_RUNTIME_DIR", runtime.path());
+    env::set_var("XDG_STATE_HOME", state.path());
+
+    // The daemon's startup: runtime files, control socket and signal handlers
+    write_pid_file().unwrap();
+    let runtime_files = RuntimeFiles;
+    let (commands_tx, commands) = mpsc::channel();
+    control::spawn_listener(&get_socket_path(), commands_tx.clone()).unwrap();
+    control::spawn_signal_listener(commands_tx).unwrap();
+    write_state(DaemonState::Transcribing);
+    let runtime_paths = [get_pid_path(), get_state_path(), get_socket_path()];
+    assert!(runtime_paths.iter().all(|path| path.exists()));
+
+    let BlockedWorker {
+        worker,
+        started,
+        release: _release,
+        typed,
+    } = blocked_worker();
+    worker.submit(Job::new(vec![0.25; 1600]));
+    started.recv().unwrap();
+
+    signal_hook::low_level::raise(SIGTERM).unwrap();
+    let request = commands.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(request.command, Command::Shutdown);
+
+    // The daemon's shutdown, with an engine that never finishes
+    let saved = worker::shut_down(worker, Duration::from_millis(100), &get_recovery_dir());
+    drop(runtime_files);
+
+    assert_eq!(saved.len(), 1);
+    assert!(saved[0].starts_with(state.path()));
+    assert_eq!(hound::WavReader::open(&saved[0]).unwrap().len(), 1600);
+    assert!(typed.lock().unwrap().is_empty());
+    for path in &runtime_paths {
+        assert!(!path.exists(), "{:?} left behind", path);
+    }
+
+    // Cleanup also runs when the daemon panics
+    write_pid_file().unwrap();
+    write_state(DaemonState::Idle);
+    let result = panic::catch_unwind(|| {
+        let _runtime_files = RuntimeFiles;
+        panic!("daemon crashed");
+    });
+    assert!(result.is_err());
+    assert!(!get_pid_path().exists() && !get_state_path().exists());
+
+    env::remove_var("XDG_RUNTIME_DIR");
+    env::remove_var("XDG_STATE_HOME");
+}
+
+#[test]
+fn test_shutdown_waits_for_job_in_progress() {
+    let BlockedWorker {
+        worker,
+        started,
+        release,
+        typed,
+    } = blocked_worker();
+    worker.submit(Job::new(vec![0.0; 10]));
+    started.recv().unwrap();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        release.send(()).unwrap();
+    });
+
+    let unfinished = worker.shutdown(Duration::from_secs(5));
+
+    assert!(unfinished.is_empty());
+    assert_eq!(*typed.lock().unwrap(), vec!["10 samples"]);
+}
+
+#[test]
+fn test_set_engine_switches_after_job_in_progress() {
+    let BlockedWorker {
+        worker,
+        started,
+        release,
+        typed,
+    } = blocked_worker();
+    worker.submit(Job::new(vec![0.0; 10]));
+    started.recv().unwrap();
+
+    worker.set_engine(Box::new(MockEngine {
+        label: "new: ",
+        ..Default::default()
+    }));
+    worker.submit(Job::new(vec![0.0; 20]));
+    release.send(()).unwrap();
+
+    assert!(worker.shutdown(Duration::from_secs(5)).is_empty());
+    assert_eq!(
+        *typed.lock().unwrap(),
+        vec!["10 samples", "new: 20 samples"]
+    );
+}
+
+fn wait_for(condition: impl Fn() -> bool) {
+    let start = Instant::now();
+    while !condition() {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// A loader that builds labelled mock engines, counting how often it ran.
+fn counting_loader(loads: &Arc<AtomicUsize>) -> LoadFn {
+    let loads = Arc::clone(loads);
+    Arc::new(move || {
+        loads.fetch_add(1, Ordering::SeqCst);
+        Ok(Box::new(MockEngine {
+            label: "reloaded: ",
+            ..Default::default()
+        }) as Box<dyn Transcriber>)
+    })
+}
+
+#[test]
+fn test_unloaded_engine_reloads_for_next_job() {
+    let (worker, typed) = mock_worker(MockEngine::default());
+    let loads = Arc::new(AtomicUsize::new(0));
+    worker.set_loader(counting_loader(&loads));
+
+    worker.unload();
+    wait_for(|| !worker.is_loaded());
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(vec![0.0; 5], tx));
+    assert_eq!(rx.recv().unwrap(), Ok("reloaded: 5 samples".to_string()));
+    assert!(worker.is_loaded());
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    worker.shutdown(Duration::from_secs(5));
+    assert_eq!(*typed.lock().unwrap(), vec!["reloaded: 5 samples"]);
+}
+
+#[test]
+fn test_preload_loads_without_a_job() {
+    let (worker, _typed) = mock_worker(MockEngine::default());
+    let loads = Arc::new(AtomicUsize::new(0));
+    worker.set_loader(counting_loader(&loads));
+
+    worker.unload();
+    wait_for(|| !worker.is_loaded());
+    worker.preload();
+    wait_for(|| worker.is_loaded());
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // Already loaded, so nothing more to do
+    worker.preload();
+    worker.submit(Job::new(vec![0.0; 5]));
+    worker.shutdown(Duration::from_secs(5));
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_unload_without_loader_keeps_engine() {
+    let (worker, _typed) = mock_worker(MockEngine::default());
+    worker.unload();
+
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(vec![0.0; 5], tx));
+    assert_eq!(rx.recv().unwrap(), Ok("5 samples".to_string()));
+    assert!(worker.is_loaded());
+    worker.shutdown(Duration::from_secs(5));
+}
+
+#[test]
+fn test_failed_reload_fails_the_job() {
+    let (worker, typed) = mock_worker(MockEngine::default());
+    worker.set_loader(Arc::new(|| Err("model file missing".to_string())));
+    worker.unload();
+    wait_for(|| !worker.is_loaded());
+
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(vec![0.0; 5], tx));
+    let result = rx.recv().unwrap();
+    assert!(result.unwrap_err().contains("model file missing"));
+    assert!(!worker.is_loaded());
+
+    // The recording is handed back to be saved, once
+    let temp = tempdir().unwrap();
+    let failed = worker.take_failed();
+    assert_eq!(failed, vec![vec![0.0; 5]]);
+    assert!(worker.take_failed().is_empty());
+    let mut saved = worker::save_recordings(temp.path(), &failed);
+
+    // Shutdown, straight after, saves what's left alongside it
+    let (tx, rx) = mpsc::channel();
+    worker.submit(Job::with_reply(vec![0.5; 7], tx));
+    assert!(rx.recv().unwrap().is_err());
+    saved.extend(worker::shut_down(
+        worker,
+        Duration::from_secs(5),
+        temp.path(),
+    ));
+    assert!(typed.lock().unwrap().is_empty());
+
+    assert_eq!(saved.len(), 2);
+    assert_ne!(saved[0], saved[1]);
+    for (path, len) in saved.iter().zip([5, 7]) {
+        assert_eq!(hound::WavReader::open(path).unwrap().len(), len);
+    }
+}
+
+#[test]
+fn test_unfinished_audio_saved_as_wav() {
+    let temp = tempdir().unwrap();
+    let path = temp.path().join("recovered.wav");
+    let samples = vec![0.0, 0.5, -0.5, 1.0];
+
+    write_wav(&path, &samples).unwrap();
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.spec().channels, 1);
+    assert_eq!(reader.spec().sample_rate, hammertalk::SAMPLE_RATE);
+    let read: Vec<f32> = reader
+        .samples::<i16>()
+        .map(|s| s.unwrap() as f32 / i16::MAX as f32)
+        .collect();
+    assert_eq!(read.len(), samples.len());
+    for (a, b) in read.iter().zip(&samples) {
+        assert!((a - b).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn test_saved_recordings_never_overwrite_each_other() {
+    let temp = tempdir().unwrap();
+    // Two saves within the same second, as after a failed job and then shutdown
+    let first = worker::save_recordings(temp.path(), &[vec![0.1; 3], vec![0.2; 4]]);
+    let second = worker::save_recordings(temp.path(), &[vec![0.3; 5]]);
+
+    let saved: Vec<_> = first.iter().chain(&second).collect();
+    assert_eq!(saved.len(), 3);
+    for (path, len) in saved.iter().zip([3, 4, 5]) {
+        assert_eq!(hound::WavReader::open(path).unwrap().len(), len);
+    }
+    assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 3);
+}